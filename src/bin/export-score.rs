@@ -0,0 +1,42 @@
+// writes the computed score without opening a window or playing anything, so that it can run where there is no display or audio device
+// build it with --no-default-features to leave out macroquad and tinyaudio entirely
+
+use pianophase::{click_track, export, humanize, music, tuning};
+
+const BPM_FOR_EIGTH_NOTE: u16 = 72 * 3;
+const SHORTEN: bool = false;
+// the same accents as in the application
+const ACCENTS: &[usize] = &[];
+// the same seed gives the same performance as in the application
+const HUMANIZATION: Option<humanize::Humanization> = None;
+// the same tuning as in the application, where the midi export bends each note to its tuning
+const TUNING: Option<tuning::Tuning> = None;
+
+const MIDI_EXPORT_PATH: &str = "output.midi";
+// set this (to Some(click_track::ClickPulse::Part2) for example) to add a click track to the midi export
+const CLICK_TRACK: Option<click_track::ClickPulse> = None;
+// the computed score, in the format described in docs/timeline.schema.json
+const TIMELINE_EXPORT_PATH: &str = "output.json";
+// subtitles and chapter markers for the exported video, one for each rehearsal mark
+const WEBVTT_EXPORT_PATH: &str = "output.vtt";
+const SRT_EXPORT_PATH: &str = "output.srt";
+const FFMETADATA_EXPORT_PATH: &str = "output.ffmetadata";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut music = if SHORTEN { music::PianoPhase::new_shortened(BPM_FOR_EIGTH_NOTE * 2) } else { music::PianoPhase::new(BPM_FOR_EIGTH_NOTE * 2) };
+    music.validate()?;
+    music.accent(ACCENTS);
+    if let Some(humanization) = HUMANIZATION {
+        humanize::Humanizer::new(humanization).update(&mut music);
+    }
+    if let Some(tuning) = TUNING {
+        tuning.apply(&mut music);
+    }
+
+    export::export_midi(&music, CLICK_TRACK, MIDI_EXPORT_PATH)?;
+    export::export_timeline(&music, TIMELINE_EXPORT_PATH)?;
+    export::export_webvtt(&music, WEBVTT_EXPORT_PATH)?;
+    export::export_srt(&music, SRT_EXPORT_PATH)?;
+    export::export_ffmetadata(&music, FFMETADATA_EXPORT_PATH)?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+// draws still images of the piece as svg files, which can be scaled for printing in program notes and on posters
+// one image is drawn at the start of each numbered module, so that each step of the phasing is shown
+// the phase chart shows every step of the piece on one page, for the concert program
+
+use num_traits::ToPrimitive;
+use pianophase::{
+    music,
+    visualizer::{self, svg::Svg},
+};
+
+const BPM_FOR_EIGTH_NOTE: u16 = 72 * 3;
+const SHORTEN: bool = false;
+// the same accents as in the application
+const ACCENTS: &[usize] = &[];
+
+const SVG_EXPORT_DIR: &str = "output-svg/";
+// the size of the images in svg units, which only matters for the default size when they are opened
+// the height follows the aspect ratio of the layout
+const SVG_WIDTH: f32 = 2048.0;
+const SVG_HEIGHT: f32 = VISUALIZER_LAYOUT.height_for_width(SVG_WIDTH as u32) as f32;
+
+const PHASE_CHART_PATH: &str = "phase-chart.svg";
+// the proportions of a4 paper, standing up
+const PHASE_CHART_WIDTH: f32 = 2100.0;
+const PHASE_CHART_HEIGHT: f32 = 2970.0;
+
+// see visualizer/layout.rs for the other layouts
+const VISUALIZER_LAYOUT: visualizer::layout::Layout = visualizer::layout::WHEELS;
+// "light", "dark", "high-contrast", or the path to a theme file
+const THEME: &str = "light";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut music = if SHORTEN { music::PianoPhase::new_shortened(BPM_FOR_EIGTH_NOTE * 2) } else { music::PianoPhase::new(BPM_FOR_EIGTH_NOTE * 2) };
+    music.validate()?;
+    music.accent(ACCENTS);
+    let visualizer = visualizer::Visualizer::new(VISUALIZER_LAYOUT, THEME)?;
+
+    let mut phase_chart = Svg::new(PHASE_CHART_WIDTH, PHASE_CHART_HEIGHT);
+    visualizer.draw_phase_chart(&mut phase_chart, &music, PHASE_CHART_WIDTH, PHASE_CHART_HEIGHT);
+    phase_chart.save(PHASE_CHART_PATH)?;
+
+    std::fs::create_dir_all(SVG_EXPORT_DIR)?;
+    for mark in &music.rehearsal_marks {
+        let mut svg = Svg::new(SVG_WIDTH, SVG_HEIGHT);
+        visualizer.draw(&mut svg, mark.time.to_f32().unwrap(), &music, None, SVG_WIDTH, SVG_HEIGHT);
+        svg.save(format!("{SVG_EXPORT_DIR}rehearsal-mark-{:02}.svg", mark.number))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use num_rational::Rational32;
+use num_traits::ToPrimitive;
+
+use crate::music::{Dynamic, PartNumber, PianoPhase};
+
+// a stretch of the piece between two rehearsal marks, for subtitles and chapter markers in exported videos
+pub struct Chapter {
+    // in seconds since the start of the piece, which is also the time of the exported frames
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+pub fn chapters(music: &PianoPhase) -> Vec<Chapter> {
+    let end_time = music.end_time();
+    music
+        .rehearsal_marks
+        .iter()
+        .enumerate()
+        .map(|(i, rehearsal_mark)| {
+            let next_time = music.rehearsal_marks.get(i + 1).map_or(end_time, |next| next.time);
+            let time = rehearsal_mark.time.to_f32().unwrap();
+
+            let section_name = music.section_at(time).map_or("", |section| section.name);
+            let title = format!("{}. {section_name} – {}", rehearsal_mark.number, describe(music, time));
+
+            Chapter { start: seconds(music, rehearsal_mark.time), end: seconds(music, next_time), title }
+        })
+        .collect()
+}
+
+// what the parts are doing at the start of a chapter, like "piano 2 fades in" or "phase 3 of 8"
+fn describe(music: &PianoPhase, time: f32) -> String {
+    let dynamic = |part_number: PartNumber| {
+        let part = music.part(part_number);
+        part.find_segment_for_time(time).map_or(Dynamic::Silent, |segment_index| part.segments[segment_index].dynamic)
+    };
+    let (part1_dynamic, part2_dynamic) = (dynamic(PartNumber::Part1), dynamic(PartNumber::Part2));
+
+    match (part1_dynamic, part2_dynamic) {
+        (Dynamic::Silent, Dynamic::Silent) => "silence".to_string(),
+        (_, Dynamic::Silent) => "piano 1 alone".to_string(),
+        (Dynamic::Silent, _) => "piano 2 alone".to_string(),
+        (Dynamic::Hairpin { from, to, curve: _ }, _) if from < to => "piano 1 fades in".to_string(),
+        (_, Dynamic::Hairpin { from, to, curve: _ }) if from < to => "piano 2 fades in".to_string(),
+        (Dynamic::Hairpin { .. }, _) => "piano 1 fades out".to_string(),
+        (_, Dynamic::Hairpin { .. }) => "piano 2 fades out".to_string(),
+        (Dynamic::Constant(_), Dynamic::Constant(_)) => {
+            let part1 = &music.part1;
+            let pattern_len = part1.find_segment_for_time(time).map_or(0, |segment_index| part1.segments[segment_index].pattern.0.len());
+            match music.phase_offset(time).map(|offset| offset.round() as usize) {
+                Some(0) | None => "in unison".to_string(),
+                Some(offset) => format!("phase {offset} of {pattern_len}"),
+            }
+        }
+    }
+}
+
+fn seconds(music: &PianoPhase, time: Rational32) -> f64 {
+    time.to_f64().unwrap() * 60.0 / music.tempo as f64
+}
@@ -0,0 +1,76 @@
+use num_rational::{Ratio, Rational32};
+use num_traits::ToPrimitive;
+
+use crate::music::{Part, PartNumber, PianoPhase};
+
+// the notes are 16th notes, so a click every 2 notes is a click on every 8th note, the same as the time signature in the midi export
+pub const NOTES_PER_CLICK: usize = 2;
+
+// which pulse the click track follows
+// following part 2 lets a pianist practicing it hear the tempo it should be phasing at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClickPulse {
+    Part1,
+    Part2,
+    // both parts, with a different sound for each part
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Click {
+    pub time: Rational32,
+    // which part's pulse the click belongs to, so that the parts can be told apart
+    pub part_number: PartNumber,
+    // the first click of each repetition of the pattern is accented
+    pub accent: bool,
+}
+
+impl ClickPulse {
+    pub fn part_numbers(self) -> &'static [PartNumber] {
+        match self {
+            ClickPulse::Part1 => &[PartNumber::Part1],
+            ClickPulse::Part2 => &[PartNumber::Part2],
+            ClickPulse::Both => &[PartNumber::Part1, PartNumber::Part2],
+        }
+    }
+}
+
+// the clicks of the whole piece, in order of time
+pub fn clicks(music: &PianoPhase, pulse: ClickPulse) -> Vec<Click> {
+    let end_time = music.end_time().to_f32().unwrap();
+    clicks_between(music, pulse, 0.0, end_time + 1.0)
+}
+
+// the clicks from start_time up to but not including end_time, in order of time
+pub fn clicks_between(music: &PianoPhase, pulse: ClickPulse, start_time: f32, end_time: f32) -> Vec<Click> {
+    let mut clicks: Vec<_> = pulse
+        .part_numbers()
+        .iter()
+        .flat_map(|&part_number| part_clicks_between(music.part(part_number), part_number, start_time, end_time))
+        .collect();
+    clicks.sort_by_key(|click| click.time);
+    clicks
+}
+
+// clicks keep going through the segments where the part rests, since the pulse is still there
+fn part_clicks_between(part: &Part, part_number: PartNumber, start_time: f32, end_time: f32) -> impl Iterator<Item = Click> + '_ {
+    // only the segments that overlap the time range are looked at
+    let first_segment = part.segments.partition_point(|segment| segment.end_time.to_f32().unwrap() <= start_time);
+    part.segments[first_segment..]
+        .iter()
+        .take_while(move |segment| segment.start_time.to_f32().unwrap() < end_time)
+        .flat_map(move |segment| {
+            (0..segment.repetitions as usize).flat_map(move |measure_number| {
+                let measure = segment.get_measure(measure_number);
+                (0..segment.pattern.0.len()).step_by(NOTES_PER_CLICK).map(move |note_index| Click {
+                    time: measure.start_time + Ratio::from_integer(note_index as i32) / segment.speed,
+                    part_number,
+                    accent: note_index == 0,
+                })
+            })
+        })
+        .filter(move |click| (start_time..end_time).contains(&click.time.to_f32().unwrap()))
+}
+
+#[cfg(test)]
+mod tests;
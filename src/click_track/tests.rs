@@ -0,0 +1,55 @@
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+
+use super::*;
+
+#[test]
+fn accents_fall_on_the_start_of_each_repetition() {
+    let music = PianoPhase::new(432);
+    for part_number in [PartNumber::Part1, PartNumber::Part2] {
+        let pulse = if part_number == PartNumber::Part1 { ClickPulse::Part1 } else { ClickPulse::Part2 };
+        let clicks = clicks(&music, pulse);
+        let part = music.part(part_number);
+        let measure_starts: Vec<_> = part
+            .segments
+            .iter()
+            .flat_map(|segment| (0..segment.repetitions as usize).map(|measure| segment.get_measure(measure).start_time))
+            .collect();
+        let accents: Vec<_> = clicks.iter().filter(|click| click.accent).map(|click| click.time).collect();
+        assert_eq!(accents, measure_starts);
+        assert!(clicks.iter().all(|click| click.part_number == part_number));
+    }
+}
+
+#[test]
+fn clicks_follow_the_phasing_of_part_2() {
+    let music = PianoPhase::new(432);
+    let clicks = clicks(&music, ClickPulse::Part2);
+    for segment in &music.part2.segments {
+        let in_segment: Vec<_> = clicks.iter().filter(|click| segment.start_time <= click.time && click.time < segment.end_time).collect();
+        assert_eq!(in_segment.len(), segment.repetitions as usize * segment.pattern.0.len().div_ceil(NOTES_PER_CLICK));
+        // the clicks are as far apart as the notes they are on
+        for (a, b) in in_segment.iter().zip(in_segment.iter().skip(1)) {
+            let notes_apart = if b.accent { segment.pattern.0.len() % NOTES_PER_CLICK } else { 0 };
+            let notes_apart = if notes_apart == 0 { NOTES_PER_CLICK } else { notes_apart };
+            assert_eq!(b.time - a.time, Ratio::from_integer(notes_apart as i32) / segment.speed);
+        }
+    }
+}
+
+#[test]
+fn clicks_between_splits_the_piece_without_losing_clicks() {
+    let music = PianoPhase::new_shortened(432);
+    let all = clicks(&music, ClickPulse::Both);
+    let end_time = music.end_time().to_f32().unwrap() + 1.0;
+    // in uneven steps, like the frames of the player
+    let mut split = Vec::new();
+    let mut time = 0.0;
+    while time < end_time {
+        split.extend(clicks_between(&music, ClickPulse::Both, time, time + 0.37));
+        time += 0.37;
+    }
+    assert_eq!(split.len(), all.len());
+    assert!(all.iter().any(|click| click.part_number == PartNumber::Part1));
+    assert!(all.iter().any(|click| click.part_number == PartNumber::Part2));
+}
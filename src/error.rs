@@ -0,0 +1,260 @@
+use std::{fmt, io, path::PathBuf};
+
+use num_rational::Rational32;
+
+use crate::music::PartNumber;
+
+// one error type for each part of the program, so that callers can tell what went wrong without reading the message
+
+// everything that is wrong with a score, found by PianoPhase::validate before anything is played
+#[derive(Debug)]
+pub struct ScoreError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    // None when the problem is with the score as a whole
+    pub location: Option<Location>,
+    pub problem: ScoreProblem,
+}
+
+// where in the score a problem is, so that it can be found in the score
+#[derive(Debug)]
+pub struct Location {
+    pub time: Rational32,
+    pub section: Option<&'static str>,
+    pub rehearsal_mark: Option<u32>,
+    pub part_number: Option<PartNumber>,
+    pub segment_index: Option<usize>,
+}
+
+// the things that would crash or be played or drawn wrong partway through the piece
+#[derive(Debug)]
+pub enum ScoreProblem {
+    // the segments of a part have to follow each other without gaps, and each one has to end after it starts
+    SegmentsOutOfOrder,
+    // a pattern without notes cannot be repeated or phased
+    EmptyPattern,
+    PartsEndAtDifferentTimes { part1: Rational32, part2: Rational32 },
+    // phasing only works when both parts play patterns of the same length
+    PatternLengthsDiffer { part1: usize, part2: usize },
+    // every phasing segment moves part 2 exactly one note ahead of part 1
+    PhaseDoesNotAdvanceOneNote { advance: Rational32 },
+    // once part 2 is a whole pattern ahead, the segment after the phasing has to end where a segment of part 1 ends
+    CatchUpDoesNotLineUp,
+    // only the pitches on the treble staff without ledger lines can be drawn
+    UndrawablePitch { pitch: u8 },
+    // every note has to start and end on a tick of the exported midi file
+    TooManyTicksPerNote { ticks: i64 },
+}
+
+// the data files that are loaded at startup: the fonts, the sound font and theme files
+#[derive(Debug)]
+pub enum AssetError {
+    Open {
+        path: PathBuf,
+        source: io::Error,
+    },
+    InvalidJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    InvalidSoundFont {
+        path: PathBuf,
+        source: rustysynth::SoundFontError,
+    },
+    #[cfg(feature = "gui")]
+    InvalidFont {
+        path: PathBuf,
+        // fontdue only describes what is wrong with a font in words
+        message: &'static str,
+    },
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    Asset(AssetError),
+    Synthesizer(rustysynth::SynthesizerError),
+}
+
+// the midi file or midi device that the notes are read from when practicing
+#[derive(Debug)]
+pub enum InputError {
+    Open { path: PathBuf, source: io::Error },
+    InvalidMidiFile { path: PathBuf, source: midly::Error },
+    TimecodeTiming { path: PathBuf },
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Write { path: PathBuf, source: io::Error },
+    Json { path: PathBuf, source: serde_json::Error },
+    // the frames are saved with a function that only takes utf-8 paths
+    NonUtf8Path(PathBuf),
+    // midi files have at most 32767 ticks per quarter note, and the notes are exported as quarter notes
+    TooManyTicksPerNote(i64),
+    // the tempo has to fit in the 24 bits of a midi tempo event, which rules out tempos below 4 notes per minute
+    TempoOutOfRange(u16),
+    // the time between two midi events has to fit in 28 bits
+    EventsTooFarApart { ticks: i32 },
+}
+
+impl fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the score has {} problem(s):", self.diagnostics.len())?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{location}: {}", self.problem),
+            None => write!(f, "{}", self.problem),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // like "Section I, rehearsal mark 3, piano 2 segment 7, note 120"
+        let mut parts = Vec::new();
+        parts.extend(self.section.map(str::to_string));
+        parts.extend(self.rehearsal_mark.map(|number| format!("rehearsal mark {number}")));
+        match (self.part_number, self.segment_index) {
+            (Some(part_number), Some(segment_index)) => parts.push(format!("{} segment {segment_index}", part_name(part_number))),
+            (Some(part_number), None) => parts.push(part_name(part_number).to_string()),
+            (None, _) => {}
+        }
+        parts.push(format!("note {}", self.time));
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl fmt::Display for ScoreProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoreProblem::SegmentsOutOfOrder => write!(f, "the segment does not start where the previous segment ends, or ends before it starts"),
+            ScoreProblem::EmptyPattern => write!(f, "the pattern has no notes"),
+            ScoreProblem::PartsEndAtDifferentTimes { part1, part2 } => write!(f, "piano 1 ends at note {part1} but piano 2 ends at note {part2}"),
+            ScoreProblem::PatternLengthsDiffer { part1, part2 } => {
+                write!(f, "piano 2 phases against a pattern of a different length ({part2} notes against {part1})")
+            }
+            ScoreProblem::PhaseDoesNotAdvanceOneNote { advance } => write!(f, "the phasing moves piano 2 {advance} notes ahead instead of 1"),
+            ScoreProblem::CatchUpDoesNotLineUp => write!(f, "piano 2 does not line up with piano 1 again after catching up"),
+            ScoreProblem::UndrawablePitch { pitch } => write!(f, "pitch {pitch} cannot be drawn on the staff"),
+            ScoreProblem::TooManyTicksPerNote { ticks } => {
+                write!(f, "{ticks} ticks per note would be needed to export the notes exactly, but midi only allows 32767")
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Open { path, source } => write!(f, "could not open {}: {source}", path.display()),
+            AssetError::InvalidJson { path, source } => write!(f, "could not read {}: {source}", path.display()),
+            AssetError::InvalidSoundFont { path, source } => write!(f, "{} is not a valid sound font: {source}", path.display()),
+            #[cfg(feature = "gui")]
+            AssetError::InvalidFont { path, message } => write!(f, "could not load the font {}: {message}", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::Asset(error) => write!(f, "{error}"),
+            AudioError::Synthesizer(source) => write!(f, "could not create the synthesizer: {source}"),
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Open { path, source } => write!(f, "could not open {}: {source}", path.display()),
+            InputError::InvalidMidiFile { path, source } => write!(f, "{} is not a valid midi file: {source}", path.display()),
+            InputError::TimecodeTiming { path } => write!(f, "{} uses timecode timing, which is not supported", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Write { path, source } => write!(f, "could not write {}: {source}", path.display()),
+            ExportError::Json { path, source } => write!(f, "could not write {}: {source}", path.display()),
+            ExportError::NonUtf8Path(path) => write!(f, "{} is not valid utf-8", path.display()),
+            ExportError::TooManyTicksPerNote(ticks) => {
+                write!(f, "{ticks} ticks per note are needed to export the notes exactly, but midi only allows 32767")
+            }
+            ExportError::TempoOutOfRange(tempo) => write!(f, "a tempo of {tempo} notes per minute cannot be written to a midi file"),
+            ExportError::EventsTooFarApart { ticks } => write!(f, "two midi events are {ticks} ticks apart, which is too far for a midi file"),
+        }
+    }
+}
+
+impl std::error::Error for ScoreError {}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Open { path: _, source } => Some(source),
+            AssetError::InvalidJson { path: _, source } => Some(source),
+            AssetError::InvalidSoundFont { path: _, source } => Some(source),
+            #[cfg(feature = "gui")]
+            AssetError::InvalidFont { path: _, message: _ } => None,
+        }
+    }
+}
+
+impl std::error::Error for AudioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AudioError::Asset(error) => error.source(),
+            AudioError::Synthesizer(source) => Some(source),
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InputError::Open { path: _, source } => Some(source),
+            InputError::InvalidMidiFile { path: _, source } => Some(source),
+            InputError::TimecodeTiming { path: _ } => None,
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Write { path: _, source } => Some(source),
+            ExportError::Json { path: _, source } => Some(source),
+            ExportError::NonUtf8Path(_)
+            | ExportError::TooManyTicksPerNote(_)
+            | ExportError::TempoOutOfRange(_)
+            | ExportError::EventsTooFarApart { .. } => None,
+        }
+    }
+}
+
+impl From<AssetError> for AudioError {
+    fn from(error: AssetError) -> Self {
+        AudioError::Asset(error)
+    }
+}
+
+fn part_name(part_number: PartNumber) -> &'static str {
+    match part_number {
+        PartNumber::Part1 => "piano 1",
+        PartNumber::Part2 => "piano 2",
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::{Div, Mul, Rem},
+    path::Path,
+};
+
+use num_rational::{Ratio, Rational32};
+
+use crate::{
+    chapters::chapters,
+    click_track::{self, ClickPulse},
+    error::ExportError,
+    music::{volume_to_velocity, FlattenedNote, Part, PartNumber, PianoPhase},
+    timeline::Timeline,
+    tuning::{pitch_bend, PITCH_BEND_RANGE_CENTS},
+};
+
+// enough ticks that humanized timing is not rounded away
+const MIN_TICKS_PER_NOTE: i64 = 96;
+// the most ticks per quarter note that a midi file can have
+pub const MAX_TICKS_PER_NOTE: i64 = 0x7fff;
+
+// the click track is on the general midi percussion channel, with wood blocks for part 1 and claves and a side stick for part 2
+const CLICK_CHANNEL: u8 = 9;
+const CLICK_LENGTH: Rational32 = Ratio::new_raw(1, 2);
+const CLICK_VELOCITY: u8 = 70;
+const ACCENTED_CLICK_VELOCITY: u8 = 110;
+fn click_key(part_number: PartNumber, accent: bool) -> u8 {
+    match (part_number, accent) {
+        (PartNumber::Part1, true) => 76,
+        (PartNumber::Part1, false) => 77,
+        (PartNumber::Part2, true) => 75,
+        (PartNumber::Part2, false) => 37,
+    }
+}
+
+// the click track is written as a third track when there is a pulse for it to follow
+pub fn export_midi(music: &PianoPhase, click_pulse: Option<ClickPulse>, output_path: impl AsRef<Path>) -> Result<(), ExportError> {
+    use midly::{
+        num::{u14, u15, u24, u28, u4, u7},
+        write_std, Format, Header, MetaMessage, MidiMessage, PitchBend, Timing, Track, TrackEvent, TrackEventKind,
+    };
+
+    // even though the music is written so that each note is a 16th note, we pretend that all of the notes are quarter notes for ease of exporting
+    let ticks_per_quarter_note = ticks_per_note(music.part1.flattened.iter().chain(&music.part2.flattened));
+    let ticks_per_quarter_note = i32::try_from(ticks_per_quarter_note)
+        .ok()
+        .filter(|&ticks| ticks as i64 <= MAX_TICKS_PER_NOTE)
+        .ok_or(ExportError::TooManyTicksPerNote(ticks_per_quarter_note))?;
+    let convert_time_to_ticks = |time: Rational32| {
+        let time_multiplied = time * Ratio::from_integer(ticks_per_quarter_note);
+        // ticks_per_note makes every time a whole number of ticks, which the midi tests check
+        debug_assert!(time_multiplied.is_integer());
+        time_multiplied.to_integer()
+    };
+    // the offsets of a humanized performance are not exact, so they are rounded to the nearest tick
+    let convert_offset_to_ticks = |offset: f32| (offset * ticks_per_quarter_note as f32).round() as i32;
+
+    fn make_track(mut events: Vec<(i32, TrackEventKind)>) -> Result<Track, ExportError> {
+        events.sort_by_key(|(ev_time, _)| *ev_time);
+
+        let mut new_events = Vec::new();
+        let mut last_time = 0;
+        for (event_time, event_kind) in events {
+            let ticks = event_time - last_time;
+            let delta = u32::try_from(ticks).ok().and_then(u28::try_from).ok_or(ExportError::EventsTooFarApart { ticks })?;
+            new_events.push(TrackEvent { delta, kind: event_kind });
+            last_time = event_time;
+        }
+
+        new_events.push(TrackEvent { delta: 0.into(), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+
+        Ok(new_events)
+    }
+    let tempo =
+        (music.tempo > 0).then(|| u24::try_from(60_000_000u32 / music.tempo as u32)).flatten().ok_or(ExportError::TempoOutOfRange(music.tempo))?;
+    // the sections and rehearsal marks go into the track of part 1 as marker events, since their times are the times of part 1
+    let marker_texts: Vec<_> = music
+        .sections
+        .iter()
+        .map(|section| (section.start_time, section.name.to_string()))
+        .chain(music.rehearsal_marks.iter().map(|rehearsal_mark| (rehearsal_mark.time, rehearsal_mark.number.to_string())))
+        .collect();
+    let marker_events: Vec<_> =
+        marker_texts.iter().map(|(time, text)| (convert_time_to_ticks(*time), TrackEventKind::Meta(MetaMessage::Marker(text.as_bytes())))).collect();
+
+    let convert_part = |part: &Part, track_number: u16, channel_number: u4, extra_events: Vec<_>| -> Result<Track, ExportError> {
+        let header_events = [
+            (0, TrackEventKind::Meta(MetaMessage::TrackNumber(Some(track_number)))),
+            (0, TrackEventKind::Meta(MetaMessage::MidiChannel(channel_number))),
+            (0, TrackEventKind::Meta(MetaMessage::Tempo(tempo))),
+            (0, TrackEventKind::Meta(MetaMessage::TimeSignature(1, 2, 24 * 2, 8))), // metronome clicks every 2 quarter notes (every 2 notes)
+        ];
+
+        let tuned = part.flattened.iter().any(|flattened_note| flattened_note.performance.cents != 0.0);
+        let controller = |controller: u8, value: u8| TrackEventKind::Midi {
+            channel: channel_number,
+            message: MidiMessage::Controller { controller: u7::new(controller), value: u7::new(value) },
+        };
+        // sets the pitch bend range (registered parameter 0) so that the bends mean the same with every player
+        let pitch_bend_range_events = if tuned {
+            vec![controller(101, 0), controller(100, 0), controller(6, (PITCH_BEND_RANGE_CENTS / 100.0) as u8), controller(38, 0)]
+        } else {
+            Vec::new()
+        };
+
+        let mut midi_events = Vec::new();
+        // the bend of the note before, and where its note off is in the events
+        let mut previous: Option<(u16, usize)> = None;
+        for flattened_note in &part.flattened {
+            let start = convert_time_to_ticks(flattened_note.time) + convert_offset_to_ticks(flattened_note.performance.time_offset);
+            if tuned {
+                // a tuned part bends every note to its pitch just before it starts, see tuning::pitch_bend
+                let bend = pitch_bend(flattened_note.performance.cents);
+                if let Some((previous_bend, previous_note_off)) = previous {
+                    if previous_bend != bend {
+                        let (end, _) = &mut midi_events[previous_note_off];
+                        *end = i32::min(*end, start);
+                    }
+                }
+                midi_events.push((
+                    start,
+                    TrackEventKind::Midi { channel: channel_number, message: MidiMessage::PitchBend { bend: PitchBend(u14::new(bend)) } },
+                ));
+                previous = Some((bend, midi_events.len() + 1));
+            }
+            midi_events.push((
+                start,
+                TrackEventKind::Midi {
+                    channel: channel_number,
+                    message: MidiMessage::NoteOn {
+                        key: flattened_note.pitch.into(),
+                        vel: u7::new(volume_to_velocity(flattened_note.performance.volume)),
+                    },
+                },
+            ));
+            midi_events.push((
+                convert_time_to_ticks(flattened_note.time + flattened_note.length)
+                    + convert_offset_to_ticks(flattened_note.performance.time_offset + flattened_note.performance.length_offset),
+                TrackEventKind::Midi { channel: channel_number, message: MidiMessage::NoteOff { key: flattened_note.pitch.into(), vel: 0.into() } },
+            ));
+        }
+        make_track(
+            header_events
+                .into_iter()
+                .chain(pitch_bend_range_events.into_iter().map(|kind| (0, kind)))
+                .chain(extra_events)
+                .chain(midi_events)
+                .collect(),
+        )
+    };
+
+    let header = Header { format: Format::Parallel, timing: Timing::Metrical(u15::new(ticks_per_quarter_note as u16)) };
+    let mut tracks = vec![convert_part(&music.part1, 0, 0.into(), marker_events)?, convert_part(&music.part2, 1, 1.into(), Vec::new())?];
+
+    if let Some(click_pulse) = click_pulse {
+        let channel = u4::new(CLICK_CHANNEL);
+        let header_events = [
+            (0, TrackEventKind::Meta(MetaMessage::TrackNumber(Some(2)))),
+            (0, TrackEventKind::Meta(MetaMessage::MidiChannel(channel))),
+            (0, TrackEventKind::Meta(MetaMessage::Tempo(tempo))),
+        ];
+        let click_events = click_track::clicks(music, click_pulse).into_iter().flat_map(|click| {
+            let key = click_key(click.part_number, click.accent).into();
+            let vel = u7::new(if click.accent { ACCENTED_CLICK_VELOCITY } else { CLICK_VELOCITY });
+            [
+                (convert_time_to_ticks(click.time), TrackEventKind::Midi { channel, message: MidiMessage::NoteOn { key, vel } }),
+                (
+                    convert_time_to_ticks(click.time + CLICK_LENGTH),
+                    TrackEventKind::Midi { channel, message: MidiMessage::NoteOff { key, vel: 0.into() } },
+                ),
+            ]
+        });
+        tracks.push(make_track(header_events.into_iter().chain(click_events).collect())?);
+    }
+
+    write_file(output_path.as_ref(), |output| write_std(&header, &tracks, output))
+}
+
+pub fn export_timeline(music: &PianoPhase, output_path: impl AsRef<Path>) -> Result<(), ExportError> {
+    let path = output_path.as_ref();
+    let output = BufWriter::new(File::create(path).map_err(|source| ExportError::Write { path: path.to_owned(), source })?);
+    serde_json::to_writer(output, &Timeline::new(music)).map_err(|source| ExportError::Json { path: path.to_owned(), source })
+}
+
+pub fn export_webvtt(music: &PianoPhase, output_path: impl AsRef<Path>) -> Result<(), ExportError> {
+    write_file(output_path.as_ref(), |output| {
+        writeln!(output, "WEBVTT")?;
+        for (i, chapter) in chapters(music).iter().enumerate() {
+            writeln!(output)?;
+            writeln!(output, "{}", i + 1)?;
+            writeln!(output, "{} --> {}", format_timestamp(chapter.start, '.'), format_timestamp(chapter.end, '.'))?;
+            writeln!(output, "{}", chapter.title)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn export_srt(music: &PianoPhase, output_path: impl AsRef<Path>) -> Result<(), ExportError> {
+    write_file(output_path.as_ref(), |output| {
+        for (i, chapter) in chapters(music).iter().enumerate() {
+            writeln!(output, "{}", i + 1)?;
+            writeln!(output, "{} --> {}", format_timestamp(chapter.start, ','), format_timestamp(chapter.end, ','))?;
+            writeln!(output, "{}", chapter.title)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    })
+}
+
+// for adding chapters to a video with ffmpeg -i video.mp4 -i chapters.txt -map_metadata 1 -codec copy output.mp4
+pub fn export_ffmetadata(music: &PianoPhase, output_path: impl AsRef<Path>) -> Result<(), ExportError> {
+    write_file(output_path.as_ref(), |output| {
+        writeln!(output, ";FFMETADATA1")?;
+        for chapter in chapters(music) {
+            writeln!(output)?;
+            writeln!(output, "[CHAPTER]")?;
+            writeln!(output, "TIMEBASE=1/1000")?;
+            writeln!(output, "START={}", (chapter.start * 1000.0).round() as u64)?;
+            writeln!(output, "END={}", (chapter.end * 1000.0).round() as u64)?;
+            // these characters have special meanings in ffmetadata files
+            let title: String = chapter.title.chars().flat_map(|c| if "=;#\\\n".contains(c) { vec!['\\', c] } else { vec![c] }).collect();
+            writeln!(output, "title={title}")?;
+        }
+        Ok(())
+    })
+}
+
+// creates the file and writes it, keeping the path in the error
+fn write_file(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> Result<(), ExportError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .and_then(|mut output| {
+            write(&mut output)?;
+            output.flush()
+        })
+        .map_err(|source| ExportError::Write { path: path.to_owned(), source })
+}
+
+// hours:minutes:seconds followed by the milliseconds, which are separated with a period in webvtt and with a comma in srt
+fn format_timestamp(seconds: f64, millisecond_separator: char) -> String {
+    let milliseconds = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{millisecond_separator}{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+// the fewest ticks per note that lets every one of the notes start and end exactly on a tick
+pub fn ticks_per_note<'a>(notes: impl IntoIterator<Item = &'a FlattenedNote>) -> i64 {
+    notes.into_iter().flat_map(|note| [*note.time.denom(), *(note.time + note.length).denom()]).map(i64::from).fold(MIN_TICKS_PER_NOTE, lcm)
+}
+
+fn lcm<T: Mul<T, Output = T> + Copy + Ord + Rem<T, Output = T> + num_traits::Zero + Div<Output = T>>(x: T, y: T) -> T {
+    x / gcd(x, y) * y
+}
+fn gcd<T: Copy + Ord + Rem<T, Output = T> + num_traits::Zero>(x: T, y: T) -> T {
+    let mut max = std::cmp::max(x, y);
+    let mut min = std::cmp::min(x, y);
+
+    while min > T::zero() {
+        let next_max = min;
+        let next_min = max % min;
+
+        max = next_max;
+        min = next_min;
+    }
+
+    max
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use num_rational::{Ratio, Rational32};
+
+use super::*;
+use crate::{
+    humanize::{Humanization, Humanizer},
+    music::PianoPhase,
+    tuning::Tuning,
+};
+
+const TEMPO: u16 = 432;
+// the parsed events of the exported shortened piece, one per line
+// after an intended change to the export, rewrite it by running the tests with UPDATE_GOLDEN_FILES=1 and check the diff
+const GOLDEN_FILE: &str = "src/export/shortened.midi.txt";
+
+// a note read back from an exported midi file
+#[derive(Debug, PartialEq)]
+struct ParsedNote {
+    pitch: u8,
+    start_tick: i64,
+    end_tick: i64,
+    velocity: u8,
+}
+
+// exports to a file of its own, since the tests run at the same time
+fn export(music: &PianoPhase, click_pulse: Option<ClickPulse>, name: &str) -> Vec<u8> {
+    let path = std::env::temp_dir().join(format!("pianophase-{}-{name}.midi", std::process::id()));
+    export_midi(music, click_pulse, &path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    bytes
+}
+
+fn ticks_per_quarter_note(smf: &Smf) -> i64 {
+    match smf.header.timing {
+        Timing::Metrical(ticks) => ticks.as_int() as i64,
+        Timing::Timecode(..) => panic!("the export uses timecode timing"),
+    }
+}
+
+// the notes of every track, in the order that they start
+fn parse_notes(smf: &Smf) -> Vec<Vec<ParsedNote>> {
+    smf.tracks
+        .iter()
+        .map(|track| {
+            let mut notes = Vec::new();
+            // the notes that are still held, by key, in the order that they were started
+            let mut held: HashMap<u8, Vec<usize>> = HashMap::new();
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int() as i64;
+                match event.kind {
+                    TrackEventKind::Midi { message: MidiMessage::NoteOn { key, vel }, .. } => {
+                        held.entry(key.as_int()).or_default().push(notes.len());
+                        notes.push(ParsedNote { pitch: key.as_int(), start_tick: tick, end_tick: tick, velocity: vel.as_int() });
+                    }
+                    TrackEventKind::Midi { message: MidiMessage::NoteOff { key, .. }, .. } => {
+                        let started = held.get_mut(&key.as_int()).filter(|started| !started.is_empty()).expect("a note ends that was never started");
+                        notes[started.remove(0)].end_tick = tick;
+                    }
+                    _ => {}
+                }
+            }
+            assert!(held.values().all(Vec::is_empty), "a note is never ended");
+            notes
+        })
+        .collect()
+}
+
+fn describe_event(tick: i64, kind: &TrackEventKind) -> String {
+    let description = match kind {
+        TrackEventKind::Midi { channel, message: MidiMessage::NoteOn { key, vel } } => format!("channel {channel} note on {key} velocity {vel}"),
+        TrackEventKind::Midi { channel, message: MidiMessage::NoteOff { key, vel } } => format!("channel {channel} note off {key} velocity {vel}"),
+        TrackEventKind::Meta(MetaMessage::Marker(text)) => format!("marker {:?}", String::from_utf8_lossy(text)),
+        kind => format!("{kind:?}"),
+    };
+    format!("{tick} {description}")
+}
+
+fn describe(smf: &Smf) -> String {
+    let mut lines = vec![format!("{:?}", smf.header)];
+    for (track_number, track) in smf.tracks.iter().enumerate() {
+        lines.push(format!("track {track_number}"));
+        let mut tick = 0;
+        for event in track {
+            tick += event.delta.as_int() as i64;
+            lines.push(describe_event(tick, &event.kind));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[test]
+fn shortened_piece_matches_golden_file() {
+    let bytes = export(&PianoPhase::new_shortened(TEMPO), None, "golden");
+    let actual = describe(&Smf::parse(&bytes).unwrap());
+
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FILE);
+    if std::env::var_os("UPDATE_GOLDEN_FILES").is_some() {
+        fs::write(&golden_path, &actual).unwrap();
+    }
+    let expected = fs::read_to_string(&golden_path).unwrap();
+    if let Some((line_number, (expected_line, actual_line))) =
+        expected.lines().zip(actual.lines()).enumerate().find(|(_, (expected_line, actual_line))| expected_line != actual_line)
+    {
+        panic!("the export differs from {GOLDEN_FILE} at line {}:\nexpected: {expected_line}\nactual:   {actual_line}", line_number + 1);
+    }
+    assert_eq!(expected.lines().count(), actual.lines().count(), "the export has a different number of events than {GOLDEN_FILE}");
+}
+
+#[test]
+fn notes_round_trip_exactly() {
+    for (name, music) in [("shortened", PianoPhase::new_shortened(TEMPO)), ("full", PianoPhase::new(TEMPO))] {
+        let smf_bytes = export(&music, None, name);
+        let smf = Smf::parse(&smf_bytes).unwrap();
+        let ticks = ticks_per_quarter_note(&smf);
+        let parsed = parse_notes(&smf);
+
+        assert_eq!(parsed.len(), 2);
+        for (part, notes) in [&music.part1, &music.part2].into_iter().zip(&parsed) {
+            assert_eq!(notes.len(), part.flattened.len());
+            for (note, parsed_note) in part.flattened.iter().zip(notes) {
+                let to_time = |tick: i64| Rational32::new(tick as i32, ticks as i32);
+                assert_eq!(parsed_note.pitch, note.pitch);
+                assert_eq!(to_time(parsed_note.start_tick), note.time);
+                assert_eq!(to_time(parsed_note.end_tick) - to_time(parsed_note.start_tick), note.length);
+                assert_eq!(parsed_note.velocity, volume_to_velocity(note.performance.volume));
+            }
+        }
+    }
+}
+
+#[test]
+fn click_track_is_a_third_track() {
+    let music = PianoPhase::new(TEMPO);
+    let smf_bytes = export(&music, Some(ClickPulse::Both), "clicks");
+    let smf = Smf::parse(&smf_bytes).unwrap();
+    let ticks = ticks_per_quarter_note(&smf);
+    let parsed = parse_notes(&smf);
+
+    assert_eq!(parsed.len(), 3);
+    let clicks = click_track::clicks(&music, ClickPulse::Both);
+    assert_eq!(parsed[2].len(), clicks.len());
+    for (click, parsed_click) in clicks.iter().zip(&parsed[2]) {
+        assert_eq!(Rational32::new(parsed_click.start_tick as i32, ticks as i32), click.time);
+        assert_eq!(parsed_click.pitch, click_key(click.part_number, click.accent));
+    }
+}
+
+#[test]
+fn tuned_notes_are_bent_as_they_start() {
+    let mut music = PianoPhase::new_shortened(TEMPO);
+    Tuning { part2_detune: 4.0, ..Tuning::JUST }.apply(&mut music);
+    let smf_bytes = export(&music, None, "tuned");
+    let smf = Smf::parse(&smf_bytes).unwrap();
+
+    for (part, track) in [&music.part1, &music.part2].into_iter().zip(&smf.tracks) {
+        // the bend that is in effect when each note starts
+        let mut bend = None;
+        let mut bends_at_note_on = Vec::new();
+        for event in track {
+            match event.kind {
+                TrackEventKind::Midi { message: MidiMessage::PitchBend { bend: new_bend }, .. } => bend = Some(new_bend.0.as_int()),
+                TrackEventKind::Midi { message: MidiMessage::NoteOn { .. }, .. } => bends_at_note_on.push(bend),
+                _ => {}
+            }
+        }
+        let expected: Vec<_> = part.flattened.iter().map(|note| Some(pitch_bend(note.performance.cents))).collect();
+        assert_eq!(bends_at_note_on, expected);
+    }
+}
+
+// humanized timing is rounded to the nearest tick, so the notes come back at most half a tick from where they were played
+#[test]
+fn humanized_notes_round_trip_to_the_nearest_tick() {
+    let mut music = PianoPhase::new_shortened(TEMPO);
+    Humanizer::new(Humanization::NATURAL).update(&mut music);
+    let smf_bytes = export(&music, None, "humanized");
+    let smf = Smf::parse(&smf_bytes).unwrap();
+    let ticks = ticks_per_quarter_note(&smf) as f32;
+    let parsed = parse_notes(&smf);
+
+    for (part, notes) in [&music.part1, &music.part2].into_iter().zip(&parsed) {
+        assert_eq!(notes.len(), part.flattened.len());
+        // with legato, a note can still be held when the next one starts, so the notes are matched by where they start
+        let mut notes: Vec<_> = notes.iter().collect();
+        notes.sort_by_key(|note| note.start_tick);
+        for (note, parsed_note) in part.flattened.iter().zip(notes) {
+            // rounding the start and the end can each be off by half a tick
+            let tolerance = 0.5 / ticks + 1e-3;
+            assert_eq!(parsed_note.pitch, note.pitch);
+            assert!((parsed_note.start_tick as f32 / ticks - note.performed_start()).abs() <= tolerance);
+            assert!((parsed_note.end_tick as f32 / ticks - note.performed_end()).abs() <= tolerance);
+            assert_eq!(parsed_note.velocity, volume_to_velocity(note.performance.volume));
+        }
+    }
+}
+
+#[test]
+fn every_note_is_on_a_tick() {
+    let music = PianoPhase::new(TEMPO);
+    let ticks = ticks_per_note(music.part1.flattened.iter().chain(&music.part2.flattened));
+    for note in music.part1.flattened.iter().chain(&music.part2.flattened) {
+        for time in [note.time, note.time + note.length] {
+            assert!((time * Ratio::from_integer(ticks as i32)).is_integer(), "note at {} is between ticks", note.time);
+        }
+    }
+    assert!(ticks <= MAX_TICKS_PER_NOTE);
+}
+
+// humanized notes can be held into the next one, which must not be retuned by the next note's bend
+#[test]
+fn humanized_tuned_notes_keep_their_own_bend() {
+    let mut music = PianoPhase::new_shortened(TEMPO);
+    Humanizer::new(Humanization::NATURAL).update(&mut music);
+    Tuning::JUST.apply(&mut music);
+    let held_into_the_next_note = |part: &Part| {
+        part.flattened.windows(2).any(|notes| notes[0].performed_end() > notes[1].performed_start() && notes[0].pitch != notes[1].pitch)
+    };
+    assert!(held_into_the_next_note(&music.part1) || held_into_the_next_note(&music.part2), "no note is held into the next one");
+    let smf_bytes = export(&music, None, "humanized-tuned");
+    let smf = Smf::parse(&smf_bytes).unwrap();
+
+    for (part, track) in [&music.part1, &music.part2].into_iter().zip(&smf.tracks) {
+        let mut bend = None;
+        // the keys that are sounding, with the bend that they started with
+        let mut held: Vec<(u8, Option<u16>)> = Vec::new();
+        let mut bends_at_note_on = Vec::new();
+        for event in track {
+            match event.kind {
+                TrackEventKind::Midi { message: MidiMessage::PitchBend { bend: new_bend }, .. } => {
+                    bend = Some(new_bend.0.as_int());
+                    assert!(held.iter().all(|&(_, held_bend)| held_bend == bend), "the bend changes while {held:?} is held");
+                }
+                TrackEventKind::Midi { message: MidiMessage::NoteOn { key, .. }, .. } => {
+                    held.push((key.as_int(), bend));
+                    bends_at_note_on.push(bend);
+                }
+                TrackEventKind::Midi { message: MidiMessage::NoteOff { key, .. }, .. } => {
+                    held.remove(held.iter().position(|&(held_key, _)| held_key == key.as_int()).unwrap());
+                }
+                _ => {}
+            }
+        }
+        let expected: Vec<_> = part.flattened.iter().map(|note| Some(pitch_bend(note.performance.cents))).collect();
+        assert_eq!(bends_at_note_on, expected);
+    }
+}
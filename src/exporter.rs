@@ -1,41 +1,87 @@
-use std::{
-    fs::File,
-    ops::{Div, Mul, Rem},
-    path::{Path, PathBuf},
+use std::path::{Path, PathBuf};
+
+use macroquad::{
+    camera::{set_camera, set_default_camera, Camera2D},
+    color::WHITE,
+    math::{vec2, Rect},
+    texture::{draw_texture_ex, render_target, DrawTextureParams, FilterMode, Image, RenderTarget},
+    window::{screen_height, screen_width},
 };
-
-use macroquad::texture::get_screen_data;
-use num_rational::{Ratio, Rational32};
 use threadpool::ThreadPool;
 
-use crate::music::{Part, PianoPhase};
+use crate::error::ExportError;
 
 pub struct Exporter {
-    output_dir: PathBuf,
+    // a string instead of a path because the frames are saved with a function that takes a string
+    output_dir: String,
     current_frame: u32,
     thread_pool: ThreadPool,
+    // in frames, worked out from the memory the waiting frames may take up
     maximum_queue_size: usize,
+
+    width: u32,
+    height: u32,
+    // frames are drawn this many times larger in each direction and then scaled down, because render targets cannot use multisampling
+    supersampling: u32,
+    // only created once the first frame is exported so that nothing is allocated when not exporting
+    render_target: Option<RenderTarget>,
 }
 
 impl Exporter {
-    pub fn new(output_dir: PathBuf, num_export_threads: usize, maximum_queue_size: usize) -> Result<Exporter, Box<dyn std::error::Error>> {
+    pub fn new(
+        output_dir: PathBuf,
+        width: u32,
+        height: u32,
+        supersampling: u32,
+        num_export_threads: usize,
+        maximum_queue_memory: usize,
+    ) -> Result<Exporter, ExportError> {
         if !output_dir.exists() {
-            std::fs::create_dir(&output_dir)?;
+            std::fs::create_dir(&output_dir).map_err(|source| ExportError::Write { path: output_dir.clone(), source })?;
         }
-        Ok(Exporter { output_dir, current_frame: 0, thread_pool: ThreadPool::new(num_export_threads), maximum_queue_size })
+        // the frames are queued after they are scaled down, and each thread holds one more frame while saving it
+        let frame_size = width as usize * height as usize * 4;
+        let maximum_queue_size = (maximum_queue_memory / frame_size).saturating_sub(num_export_threads);
+        let output_dir = output_dir.into_os_string().into_string().map_err(|output_dir| ExportError::NonUtf8Path(output_dir.into()))?;
+        Ok(Exporter {
+            output_dir,
+            current_frame: 0,
+            thread_pool: ThreadPool::new(num_export_threads),
+            maximum_queue_size,
+            width,
+            height,
+            supersampling,
+            render_target: None,
+        })
     }
 
-    pub fn export_frame(&mut self) {
-        let screen_image = get_screen_data();
+    // draw is given the size to draw at and draws the frame into a texture that is the export size instead of onto the window,
+    // so that the export resolution does not depend on the window size
+    pub fn export_frame(&mut self, draw: impl FnOnce(f32, f32)) {
+        let (render_width, render_height) = (self.width * self.supersampling, self.height * self.supersampling);
+        let render_target = self.render_target.get_or_insert_with(|| {
+            let render_target = render_target(render_width, render_height);
+            render_target.texture.set_filter(FilterMode::Linear);
+            render_target
+        });
+
+        set_camera(&Camera2D {
+            render_target: Some(render_target.clone()),
+            ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, render_width as f32, render_height as f32))
+        });
+        draw(render_width as f32, render_height as f32);
+        set_default_camera();
 
-        let mut output_path = self.output_dir.clone();
-        output_path.push(format!("frame{:06}", self.current_frame));
-        output_path.set_extension("png");
+        // scaled down before it is queued, so that only one frame at the supersampled size is ever in memory
+        let image = downsample(render_target.texture.get_texture_data(), self.supersampling);
+
+        // the output directory is utf-8, so the whole path is too
+        let output_path = Path::new(&self.output_dir).join(format!("frame{:06}.png", self.current_frame)).to_string_lossy().into_owned();
 
         self.thread_pool.execute({
             let current_frame = self.current_frame;
             move || {
-                screen_image.export_png(output_path.to_str().unwrap());
+                image.export_png(&output_path);
                 println!("frame {} exported", current_frame);
             }
         });
@@ -51,113 +97,46 @@ impl Exporter {
         }
     }
 
+    // shows the last exported frame on the window, scaled to fit
+    pub fn draw_preview(&self) {
+        let Some(render_target) = &self.render_target else {
+            return;
+        };
+        let scale = f32::min(screen_width() / self.width as f32, screen_height() / self.height as f32);
+        let (preview_width, preview_height) = (self.width as f32 * scale, self.height as f32 * scale);
+        draw_texture_ex(
+            &render_target.texture,
+            (screen_width() - preview_width) * 0.5,
+            (screen_height() - preview_height) * 0.5,
+            WHITE,
+            DrawTextureParams { dest_size: Some(vec2(preview_width, preview_height)), flip_y: true, ..Default::default() },
+        );
+    }
+
     pub fn finish(&self) {
         println!("waiting for {} frames to finish exporting; total frame count {}", self.thread_pool.queued_count(), self.current_frame);
         self.thread_pool.join();
     }
+}
 
-    pub fn export_midi(&self, music: &PianoPhase, output_path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
-        use midly::{
-            num::{u15, u24, u28, u4, u7},
-            write_std, Format, Header, MetaMessage, MidiMessage, Timing, Track, TrackEvent, TrackEventKind,
-        };
-
-        // even though the music is written so that each note is a 16th note, we pretend that all of the notes are quarter notes for ease of exporting
-        let ticks_per_quarter_note = music
-            .part1
-            .flattened
-            .iter()
-            .chain(music.part2.flattened.iter())
-            .flat_map(|note| [*note.time.denom(), *(note.time + note.length).denom()])
-            .reduce(lcm)
-            .unwrap();
-        let convert_time_to_ticks = |time: Rational32| {
-            let time_multiplied = time * Ratio::from_integer(ticks_per_quarter_note);
-            assert_eq!(*time_multiplied.numer(), time_multiplied.to_integer()); // this is just a sanity check because this should be mathematically true anyways
-            time_multiplied.to_integer()
-        };
-
-        fn make_track(mut events: Vec<(i32, TrackEventKind)>) -> Track {
-            events.sort_by_key(|(ev_time, _)| *ev_time);
-
-            let mut new_events = Vec::new();
-            let mut last_time = 0;
-            for (event_time, event_kind) in events {
-                new_events.push(TrackEvent { delta: u28::try_from((event_time - last_time).try_into().unwrap()).unwrap(), kind: event_kind });
-                last_time = event_time;
+// averages each factor by factor block of pixels into one pixel
+fn downsample(image: Image, factor: u32) -> Image {
+    if factor == 1 {
+        return image;
+    }
+    let factor = factor as usize;
+    let (width, height) = (image.width as usize / factor, image.height as usize / factor);
+    let mut bytes = vec![0; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            for channel in 0..4 {
+                let sum: u32 = (0..factor)
+                    .flat_map(|dy| (0..factor).map(move |dx| (dx, dy)))
+                    .map(|(dx, dy)| image.bytes[((y * factor + dy) * image.width as usize + x * factor + dx) * 4 + channel] as u32)
+                    .sum();
+                bytes[(y * width + x) * 4 + channel] = (sum / (factor * factor) as u32) as u8;
             }
-
-            new_events.push(TrackEvent { delta: 0.into(), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
-
-            new_events
         }
-        let convert_part = |part: &Part, track_number: u16, channel_number: u4| -> Track {
-            let header_events = [
-                (0, TrackEventKind::Meta(MetaMessage::TrackNumber(Some(track_number)))),
-                (0, TrackEventKind::Meta(MetaMessage::MidiChannel(channel_number))),
-                (0, TrackEventKind::Meta(MetaMessage::Tempo(u24::try_from(60_000_000u32 / music.tempo as u32).unwrap()))),
-                (0, TrackEventKind::Meta(MetaMessage::TimeSignature(1, 2, 24 * 2, 8))), // metronome clicks every 2 quarter notes (every 2 notes)
-            ];
-
-            let midi_events: Vec<_> = part
-                .flattened
-                .iter()
-                .flat_map(|flattened_note| {
-                    [
-                        (
-                            convert_time_to_ticks(flattened_note.time),
-                            TrackEventKind::Midi {
-                                channel: channel_number,
-                                message: MidiMessage::NoteOn {
-                                    key: flattened_note.pitch.into(),
-                                    vel: u7::new((flattened_note.volume * u7::max_value().as_int() as f32).floor() as u8),
-                                },
-                            },
-                        ),
-                        (
-                            convert_time_to_ticks(flattened_note.time + flattened_note.length),
-                            TrackEventKind::Midi {
-                                channel: channel_number,
-                                message: MidiMessage::NoteOff { key: flattened_note.pitch.into(), vel: 0.into() },
-                            },
-                        ),
-                    ]
-                })
-                .collect();
-
-            make_track(header_events.into_iter().chain(midi_events).collect())
-        };
-
-        let output_file = File::create(output_path)?;
-
-        let header = Header {
-            format: Format::Parallel,
-            timing: Timing::Metrical(
-                u15::try_from(ticks_per_quarter_note.try_into().expect("ticks per beat cannot fit into a u16"))
-                    .expect("ticks per beat cannot fit into a u15"),
-            ),
-        };
-
-        write_std(&header, [&convert_part(&music.part1, 0, 0.into()), &convert_part(&music.part2, 1, 1.into())], output_file)?;
-
-        Ok(())
-    }
-}
-
-fn lcm<T: Mul<T, Output = T> + Copy + Ord + Rem<T, Output = T> + num_traits::Zero + Div<Output = T>>(x: T, y: T) -> T {
-    x * y / gcd(x, y)
-}
-fn gcd<T: Copy + Ord + Rem<T, Output = T> + num_traits::Zero>(x: T, y: T) -> T {
-    let mut max = std::cmp::max(x, y);
-    let mut min = std::cmp::min(x, y);
-
-    while min > T::zero() {
-        let next_max = min;
-        let next_min = max % min;
-
-        max = next_max;
-        min = next_min;
     }
-
-    max
+    Image { bytes, width: width as u16, height: height as u16 }
 }
@@ -0,0 +1,88 @@
+use num_traits::ToPrimitive;
+
+use crate::{
+    music::{DynamicLevel, Hand, Part, PartNumber, PianoPhase},
+    util::Random,
+};
+
+// the furthest a note can be played from where it is written, in notes
+// this is kept well below the length of a note so that the notes of a part are still played in order
+pub const MAX_TIME_OFFSET: f32 = 0.25;
+
+// makes the playback and midi export sound like two pianists instead of a sequencer by changing the performance of every note
+// all of the randomness comes from the seed, so the same seed always gives the same performance
+#[derive(Clone, Copy, Debug)]
+pub struct Humanization {
+    pub seed: u64,
+    // the standard deviation of how early or late the notes are played, in notes
+    pub timing_jitter: f32,
+    // the standard deviation of how much the volume of each note is changed, as a fraction of its volume
+    pub volume_variation: f32,
+    // how much each pianist shapes the pattern, as a fraction of the volume
+    // every position in the pattern gets its own accent that stays the same for every repetition, and each hand has its own overall balance
+    pub accent: f32,
+    // how much longer the notes are held than written, in notes
+    pub legato: f32,
+}
+
+impl Humanization {
+    pub const NATURAL: Humanization = Humanization { seed: 0, timing_jitter: 0.02, volume_variation: 0.06, accent: 0.1, legato: 0.08 };
+}
+
+// humanizes the notes of the music as they are added
+pub struct Humanizer {
+    humanization: Humanization,
+    humanized_up_to: [usize; 2],
+}
+
+impl Humanizer {
+    pub fn new(humanization: Humanization) -> Humanizer {
+        Humanizer { humanization, humanized_up_to: [0, 0] }
+    }
+
+    // the performance of a note only depends on the seed and on where the note is, so notes that are added later (like when phasing live) are humanized the same way
+    pub fn update(&mut self, music: &mut PianoPhase) {
+        for (i, (part_number, part)) in [(PartNumber::Part1, &mut music.part1), (PartNumber::Part2, &mut music.part2)].into_iter().enumerate() {
+            self.humanize_part(part, part_number, self.humanized_up_to[i]);
+            self.humanized_up_to[i] = part.flattened.len();
+        }
+    }
+
+    fn humanize_part(&self, part: &mut Part, part_number: PartNumber, start: usize) {
+        let Humanization { seed, timing_jitter, volume_variation, accent, legato } = self.humanization;
+        let part_key = part_number as u64;
+
+        for note_index in start..part.flattened.len() {
+            let note = &part.flattened[note_index];
+            let segment = &part.segments[note.segment_index];
+            let position_in_pattern = note.position_in_pattern(part) as u64;
+            let pattern_len = segment.pattern.0.len() as u64;
+            let hand = match note.hand {
+                Hand::Left => 0,
+                Hand::Right => 1,
+            };
+
+            let mut note_random = Random::new(&[seed, part_key, note_index as u64]);
+            // the first note cannot be played before the piece starts
+            let time_offset = (note_random.normal() * timing_jitter).clamp(-MAX_TIME_OFFSET, MAX_TIME_OFFSET).max(-note.time.to_f32().unwrap());
+            let length_offset = (legato + note_random.normal() * timing_jitter).clamp(0.0, MAX_TIME_OFFSET);
+            let variation = 1.0 + note_random.normal() * volume_variation;
+            let pattern_accent = 1.0 + Random::new(&[seed, part_key, pattern_len, position_in_pattern]).normal() * accent;
+            let hand_balance = 1.0 + Random::new(&[seed, part_key, pattern_len, hand]).normal() * accent * 0.5;
+
+            // humanized notes are played around the middle of the velocities of the dynamic they are written at, so that they can be played
+            // both louder and quieter than written without being played at another dynamic, and notes that are written silent stay silent
+            let volumes = DynamicLevel::at_volume(note.volume).volumes();
+            let middle = note.volume - (volumes.end() - volumes.start()) * 0.5;
+            let volume = (middle * variation * pattern_accent * hand_balance).clamp(*volumes.start(), *volumes.end());
+
+            let note = &mut part.flattened[note_index];
+            note.performance.time_offset = time_offset;
+            note.performance.length_offset = length_offset;
+            note.performance.volume = volume;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,45 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::music::Performance;
+
+fn humanized(humanization: Humanization) -> PianoPhase {
+    let mut music = PianoPhase::new_shortened(432);
+    Humanizer::new(humanization).update(&mut music);
+    music
+}
+
+fn performances(music: &PianoPhase) -> Vec<[f32; 3]> {
+    let notes = music.part1.flattened.iter().chain(&music.part2.flattened);
+    notes.map(|note| [note.performance.time_offset, note.performance.length_offset, note.performance.volume]).collect()
+}
+
+#[test]
+fn the_same_seed_gives_the_same_performance() {
+    assert_eq!(performances(&humanized(Humanization::NATURAL)), performances(&humanized(Humanization::NATURAL)));
+}
+
+#[test]
+fn different_seeds_give_different_performances() {
+    let natural = performances(&humanized(Humanization::NATURAL));
+    let other_seed = performances(&humanized(Humanization { seed: 1, ..Humanization::NATURAL }));
+    let different = natural.iter().zip(&other_seed).filter(|(a, b)| a[0] != b[0] && a[1] != b[1]).count();
+    // a few notes can be clamped the same way with both seeds
+    assert!(different > natural.len() * 9 / 10, "only {different} of {} notes are played differently", natural.len());
+}
+
+#[test]
+fn humanized_notes_stay_close_to_how_they_are_written() {
+    // much more than the natural timing, so that the offsets have to be clamped
+    let music = humanized(Humanization { timing_jitter: 0.5, ..Humanization::NATURAL });
+    for part in [&music.part1, &music.part2] {
+        for note in &part.flattened {
+            let Performance { time_offset, length_offset, volume, cents: _ } = note.performance;
+            assert!((-MAX_TIME_OFFSET..=MAX_TIME_OFFSET).contains(&time_offset), "{note:?}");
+            assert!((0.0..=MAX_TIME_OFFSET).contains(&length_offset), "{note:?}");
+            assert!(note.performed_start() >= 0.0, "{note:?}");
+            assert_eq!(DynamicLevel::at_volume(volume), DynamicLevel::at_volume(note.volume), "{note:?} is played at another dynamic");
+        }
+        assert!(part.flattened.iter().tuple_windows().all(|(a, b)| a.performed_start() < b.performed_start()), "the notes are played out of order");
+    }
+}
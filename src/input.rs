@@ -0,0 +1,169 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Read,
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+    time::Instant,
+};
+
+use midly::{live::LiveEvent, stream::MidiStream, MetaMessage, MidiMessage, Smf, Timing as MidiTiming, TrackEventKind};
+
+use crate::{error::InputError, music::PianoPhase, timing::Timing};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    NoteOn { time: f32, pitch: u8, velocity: u8 },
+    NoteOff { time: f32, pitch: u8 },
+}
+
+// something that notes can be played into
+// the times of the events are in musical time (the same units as FlattenedNote::time)
+pub trait InputSource {
+    // returns all of the events that happened since the last call to poll, in order
+    fn poll(&mut self, timing: &Timing, music: &PianoPhase) -> Vec<InputEvent>;
+}
+
+// an input source that replays a fixed list of events as the time passes them
+pub struct ScriptedInput {
+    events: VecDeque<InputEvent>,
+}
+
+// reads raw midi bytes from a device file such as /dev/snd/midiC1D0
+pub struct MidiDeviceInput {
+    receiver: Receiver<(Instant, InputEvent)>,
+}
+
+impl InputEvent {
+    pub fn time(&self) -> f32 {
+        match self {
+            InputEvent::NoteOn { time, .. } | InputEvent::NoteOff { time, .. } => *time,
+        }
+    }
+
+    fn from_midi_message(time: f32, message: MidiMessage) -> Option<InputEvent> {
+        match message {
+            // a note on with velocity 0 is the same as a note off
+            MidiMessage::NoteOn { key, vel } if vel == 0 => Some(InputEvent::NoteOff { time, pitch: key.as_int() }),
+            MidiMessage::NoteOn { key, vel } => Some(InputEvent::NoteOn { time, pitch: key.as_int(), velocity: vel.as_int() }),
+            MidiMessage::NoteOff { key, vel: _ } => Some(InputEvent::NoteOff { time, pitch: key.as_int() }),
+            _ => None,
+        }
+    }
+}
+
+impl ScriptedInput {
+    pub fn new(mut events: Vec<InputEvent>) -> ScriptedInput {
+        events.sort_by(|a, b| a.time().total_cmp(&b.time()));
+        ScriptedInput { events: events.into() }
+    }
+
+    // reads the note events of a midi file, optionally only the ones on a single channel
+    // the tempo events in the file are used to convert the events to real time, which is then converted to musical time using the tempo of the music
+    pub fn from_midi_file(path: impl AsRef<Path>, channel: Option<u8>, music: &PianoPhase) -> Result<ScriptedInput, InputError> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)).map_err(|source| InputError::Open { path: path.to_owned(), source })?;
+        let smf = Smf::parse(&bytes).map_err(|source| InputError::InvalidMidiFile { path: path.to_owned(), source })?;
+
+        let ticks_per_beat = match smf.header.timing {
+            MidiTiming::Metrical(ticks_per_beat) => ticks_per_beat.as_int() as f64,
+            MidiTiming::Timecode(_, _) => return Err(InputError::TimecodeTiming { path: path.to_owned() }),
+        };
+
+        // all of the events with absolute tick times, from every track
+        let mut events: Vec<(u64, TrackEventKind)> = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                events.push((tick, event.kind));
+            }
+        }
+        events.sort_by_key(|(tick, _)| *tick);
+
+        let mut input_events = Vec::new();
+        let mut microseconds_per_beat = 500_000.0; // 120 bpm is the default tempo of a midi file
+        let mut last_tick = 0;
+        let mut seconds = 0.0;
+        for (tick, kind) in events {
+            seconds += (tick - last_tick) as f64 / ticks_per_beat * microseconds_per_beat / 1_000_000.0;
+            last_tick = tick;
+
+            match kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => microseconds_per_beat = tempo.as_int() as f64,
+                TrackEventKind::Midi { channel: event_channel, message } if channel.is_none_or(|c| c == event_channel.as_int()) => {
+                    let time = (seconds * music.tempo as f64 / 60.0) as f32;
+                    input_events.extend(InputEvent::from_midi_message(time, message));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ScriptedInput::new(input_events))
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self, timing: &Timing, music: &PianoPhase) -> Vec<InputEvent> {
+        let current_time = timing.current_musical_time(music);
+
+        let mut result = Vec::new();
+        while self.events.front().is_some_and(|event| event.time() <= current_time) {
+            result.extend(self.events.pop_front());
+        }
+        result
+    }
+}
+
+impl MidiDeviceInput {
+    pub fn open(path: impl AsRef<Path>) -> Result<MidiDeviceInput, InputError> {
+        let mut device = File::open(path.as_ref()).map_err(|source| InputError::Open { path: path.as_ref().to_owned(), source })?;
+        let (sender, receiver) = channel();
+
+        // reading from the device blocks, so it happens on its own thread
+        // the events are timestamped as soon as they arrive so that the timing feedback does not depend on the frame rate
+        std::thread::spawn(move || {
+            let mut stream = MidiStream::new();
+            let mut buffer = [0; 256];
+            while let Ok(amount_read) = device.read(&mut buffer) {
+                if amount_read == 0 {
+                    break;
+                }
+                let now = Instant::now();
+                let mut disconnected = false;
+                stream.feed(&buffer[..amount_read], |event| {
+                    if let LiveEvent::Midi { channel: _, message } = event {
+                        // the time gets filled in when the event is polled
+                        if let Some(input_event) = InputEvent::from_midi_message(0.0, message) {
+                            disconnected |= sender.send((now, input_event)).is_err();
+                        }
+                    }
+                });
+                if disconnected {
+                    break;
+                }
+            }
+        });
+
+        Ok(MidiDeviceInput { receiver })
+    }
+}
+
+impl InputSource for MidiDeviceInput {
+    fn poll(&mut self, timing: &Timing, music: &PianoPhase) -> Vec<InputEvent> {
+        self.receiver
+            .try_iter()
+            .map(|(instant, event)| {
+                let time = timing.musical_time_at_instant(instant, music);
+                match event {
+                    InputEvent::NoteOn { time: _, pitch, velocity } => InputEvent::NoteOn { time, pitch, velocity },
+                    InputEvent::NoteOff { time: _, pitch } => InputEvent::NoteOff { time, pitch },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,59 @@
+use num_traits::ToPrimitive;
+
+use super::*;
+use crate::{
+    export::export_midi,
+    music::{volume_to_velocity, PianoPhase},
+};
+
+// enough frames per second that several of them fall between two notes
+const FPS: u32 = 60;
+const TEMPO: u16 = 432;
+// the tempo in a midi file is a whole number of microseconds per beat, so it can be off by up to one microsecond out of a beat
+const TEMPO_ROUNDING: f32 = TEMPO as f32 / 60_000_000.0;
+
+#[test]
+fn scripted_input_plays_the_events_as_the_time_passes_them() {
+    let music = PianoPhase::new(TEMPO);
+    let events = [
+        InputEvent::NoteOff { time: 1.0, pitch: 64 },
+        InputEvent::NoteOn { time: 0.0, pitch: 64, velocity: 100 },
+        InputEvent::NoteOn { time: 1.0, pitch: 66, velocity: 90 },
+    ];
+    let mut input = ScriptedInput::new(events.to_vec());
+    let mut timing = Timing::new(Some(FPS));
+
+    let mut polled = Vec::new();
+    while timing.current_musical_time(&music) < 2.0 {
+        for event in input.poll(&timing, &music) {
+            // no event comes before its time
+            assert!(event.time() <= timing.current_musical_time(&music));
+            polled.push(event);
+        }
+        timing.update();
+    }
+    // in order of time, and the events at the same time in the order they were given
+    assert_eq!(polled, [events[1], events[0], events[2]]);
+    assert!(input.poll(&timing, &music).is_empty());
+}
+
+#[test]
+fn midi_files_are_read_in_musical_time() {
+    let music = PianoPhase::new_shortened(TEMPO);
+    let path = std::env::temp_dir().join(format!("pianophase-{}-input.midi", std::process::id()));
+    export_midi(&music, None, &path).unwrap();
+    let input = ScriptedInput::from_midi_file(&path, Some(0), &music);
+    std::fs::remove_file(&path).unwrap();
+
+    let note_ons: Vec<_> = input.unwrap().events.into_iter().filter(|event| matches!(event, InputEvent::NoteOn { .. })).collect();
+    // a note on without velocity is read as a note off, like at the start of a crescendo
+    let notes: Vec<_> = music.part1.flattened.iter().filter(|note| volume_to_velocity(note.performance.volume) > 0).collect();
+    assert_eq!(note_ons.len(), notes.len());
+    for (event, note) in note_ons.iter().zip(notes) {
+        let InputEvent::NoteOn { time, pitch, velocity: _ } = *event else { unreachable!() };
+        assert_eq!(pitch, note.pitch);
+        // the times drift by as much as the tempo was rounded
+        let tolerance = note.time.to_f32().unwrap() * TEMPO_ROUNDING + 1e-4;
+        assert!((time - note.time.to_f32().unwrap()).abs() < tolerance, "{time} instead of {}", note.time);
+    }
+}
@@ -0,0 +1,24 @@
+// the score model, timing, playback, export and visualization of piano phase, so that other tools can reuse them
+// the application itself is in main.rs
+// the parts that need a window, an audio device or png export are behind the gui, audio-device and png-export features, see Cargo.toml
+
+pub mod chapters;
+pub mod click_track;
+pub mod error;
+pub mod export;
+#[cfg(feature = "png-export")]
+pub mod exporter;
+pub mod humanize;
+pub mod input;
+pub mod music;
+pub mod player;
+pub mod practice;
+pub mod resultant;
+pub mod tap_phase;
+pub mod timeline;
+pub mod timing;
+pub mod tuning;
+pub mod util;
+pub mod validation;
+#[cfg(feature = "gui")]
+pub mod visualizer;
@@ -1,40 +1,80 @@
 use std::time::Duration;
 
 use macroquad::{
-    input::{is_key_pressed, is_quit_requested, prevent_quit, KeyCode},
+    color::BLACK,
+    input::{is_key_down, is_key_pressed, is_quit_requested, prevent_quit, KeyCode},
     prelude::{next_frame, Conf},
+    window::clear_background,
+};
+use pianophase::{
+    click_track, export, exporter, humanize, input, music, player, practice, tap_phase, timing, tuning,
+    visualizer::{self, canvas::Screen},
 };
-
-mod exporter;
-mod music;
-mod player;
-mod timing;
-mod util;
-mod visualizer;
 
 const BPM_FOR_EIGTH_NOTE: u16 = 72 * 3;
 const SHORTEN: bool = false;
-
-const WINDOW_WIDTH: i32 = 4096;
-const WINDOW_HEIGHT: i32 = 4096;
+// the positions in each pattern that are accented (&[0] accents the first note of every pattern, for example), which the score leaves to the performers
+const ACCENTS: &[usize] = &[];
+// set this (to Some(humanize::Humanization::NATURAL) for example) to make the playback and the midi export sound less mechanical
+// the same seed always gives the same performance
+const HUMANIZATION: Option<humanize::Humanization> = None;
+// set this (to Some(tuning::Tuning::JUST) or Some(tuning::Tuning::TWO_PIANOS) for example) to hear the piece in another temperament or with the pianos out of tune with each other
+// the midi export bends each note to its tuning
+const TUNING: Option<tuning::Tuning> = None;
+
+// see visualizer/layout.rs for the other layouts
+const VISUALIZER_LAYOUT: visualizer::layout::Layout = visualizer::layout::SQUARE;
+// "light", "dark", "high-contrast", or the path to a theme file
+const THEME: &str = "light";
+
+// the heights follow the aspect ratio of the layout
+const WINDOW_WIDTH: u32 = 1024;
+const WINDOW_HEIGHT: u32 = VISUALIZER_LAYOUT.height_for_width(WINDOW_WIDTH);
 
 const EXPORT: bool = true;
 const MIDI_EXPORT_PATH: &str = "output.midi";
+// set this (to Some(click_track::ClickPulse::Part2) for example) to add a click track to the midi export and to the playback
+const CLICK_TRACK: Option<click_track::ClickPulse> = None;
+// the computed score, in the format described in docs/timeline.schema.json
+const TIMELINE_EXPORT_PATH: &str = "output.json";
+// subtitles and chapter markers for the exported video, one for each rehearsal mark
+const WEBVTT_EXPORT_PATH: &str = "output.vtt";
+const SRT_EXPORT_PATH: &str = "output.srt";
+const FFMETADATA_EXPORT_PATH: &str = "output.ffmetadata";
 const FRAMES_EXPORT_DIR: &str = "output/";
+// the size of the exported frames, which does not depend on the size of the window
+const EXPORT_WIDTH: u32 = 4096;
+const EXPORT_HEIGHT: u32 = VISUALIZER_LAYOUT.height_for_width(EXPORT_WIDTH);
+// the frames are drawn this many times larger in each direction, so at 8192x8192 here, which takes up 256 MiB while a frame is drawn and scaled down
+const EXPORT_SUPERSAMPLING: u32 = 2;
 const EXPORT_FPS: u32 = 60;
 const NUM_EXPORT_THREADS: usize = 8;
-const MAX_EXPORT_QUEUE_SIZE: usize = 100;
+// how much memory the frames waiting to be saved can take up before drawing waits for them, in bytes
+// each frame takes up width * height * 4 bytes, 64 MiB at 4096x4096, so this is about 32 frames
+const MAX_EXPORT_QUEUE_MEMORY: usize = 2 << 30;
 
 const WAIT_FOR_FRAMES_ON_EXPORT: bool = true;
 const PLAY_ON_EXPORT: bool = true;
 
+// set this to practice one of the parts by playing it live while the other part is played back
+const PRACTICE_PART: Option<music::PartNumber> = None;
+const PRACTICE_MIDI_DEVICE: &str = "/dev/snd/midiC1D0";
+// if this is set, the notes are read from this midi file instead of from the midi device
+const PRACTICE_MIDI_FILE: Option<&str> = None;
+const PRACTICE_MIDI_FILE_CHANNEL: Option<u8> = None;
+
+// set this to phase part 2 live by holding down TAP_PHASE_KEY instead of following the phasing in the score
+const TAP_PHASE: bool = false;
+const TAP_PHASE_KEY: KeyCode = KeyCode::Up;
+
 fn window_conf() -> Conf {
     Conf {
         window_title: "Piano Phase".to_string(),
-        window_width: WINDOW_WIDTH,
-        window_height: WINDOW_HEIGHT,
+        window_width: WINDOW_WIDTH as i32,
+        window_height: WINDOW_HEIGHT as i32,
         sample_count: 4,
-        window_resizable: false,
+        window_resizable: true,
+        high_dpi: true,
         ..Default::default()
     }
 }
@@ -42,19 +82,51 @@ fn window_conf() -> Conf {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     prevent_quit();
 
-    let music = if SHORTEN { music::PianoPhase::new_shortened(BPM_FOR_EIGTH_NOTE * 2) } else { music::PianoPhase::new(BPM_FOR_EIGTH_NOTE * 2) };
+    let mut music = if SHORTEN { music::PianoPhase::new_shortened(BPM_FOR_EIGTH_NOTE * 2) } else { music::PianoPhase::new(BPM_FOR_EIGTH_NOTE * 2) };
+    music.validate()?;
+    music.accent(ACCENTS);
 
     let mut timing = timing::Timing::new(if EXPORT { Some(EXPORT_FPS) } else { None });
-    let mut exporter = exporter::Exporter::new(FRAMES_EXPORT_DIR.into(), NUM_EXPORT_THREADS, MAX_EXPORT_QUEUE_SIZE)?;
-    let mut player = player::Player::new()?;
-    let mut visualizer = visualizer::Visualizer::new().await?;
-
-    if EXPORT {
-        exporter.export_midi(&music, MIDI_EXPORT_PATH)?;
-    }
+    let mut exporter = exporter::Exporter::new(
+        FRAMES_EXPORT_DIR.into(),
+        EXPORT_WIDTH,
+        EXPORT_HEIGHT,
+        EXPORT_SUPERSAMPLING,
+        NUM_EXPORT_THREADS,
+        MAX_EXPORT_QUEUE_MEMORY,
+    )?;
     let should_play = if EXPORT { PLAY_ON_EXPORT } else { true };
     let should_wait_for_frames = if EXPORT { WAIT_FOR_FRAMES_ON_EXPORT } else { true };
+    // the sound font is not even loaded when nothing is played
+    let mut player = if should_play { Some(player::Player::new()?) } else { None };
+    let visualizer = visualizer::Visualizer::new(VISUALIZER_LAYOUT, THEME)?;
+
+    let mut tap_phase = if TAP_PHASE { Some(tap_phase::TapPhase::new(&mut music)) } else { None };
+    let mut humanizer = HUMANIZATION.map(humanize::Humanizer::new);
+    if let Some(humanizer) = &mut humanizer {
+        humanizer.update(&mut music);
+    }
+    if let Some(tuning) = TUNING {
+        tuning.apply(&mut music);
+    }
 
+    let mut practice = PRACTICE_PART.map(|part_number| practice::PracticeSession::new(part_number, &music));
+    let mut practice_input: Option<Box<dyn input::InputSource>> = match (PRACTICE_PART, PRACTICE_MIDI_FILE) {
+        (None, _) => None,
+        (Some(_), Some(midi_file)) => Some(Box::new(input::ScriptedInput::from_midi_file(midi_file, PRACTICE_MIDI_FILE_CHANNEL, &music)?)),
+        (Some(_), None) => Some(Box::new(input::MidiDeviceInput::open(PRACTICE_MIDI_DEVICE)?)),
+    };
+    if let (Some(part_number), Some(player)) = (PRACTICE_PART, &mut player) {
+        player.mute_part(part_number);
+    }
+    if let Some(player) = &mut player {
+        player.set_click_track(CLICK_TRACK);
+    }
+
+    // when phasing live, part 2 is only known once the whole piece has been played
+    if EXPORT && tap_phase.is_none() {
+        export_score(&music)?;
+    }
     loop {
         if !EXPORT {
             if is_key_pressed(KeyCode::Right) {
@@ -71,17 +143,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         if is_quit_requested() {
             break;
         }
-        if EXPORT && timing.should_end(&music) {
+        if (EXPORT || practice.is_some()) && timing.should_end(&music) {
             break;
         }
 
-        visualizer.update(&timing, &music);
-        if should_play {
-            player.update(&timing, &music);
+        if let Some(tap_phase) = &mut tap_phase {
+            let current_time = timing.current_musical_time(&music);
+            tap_phase.update(&mut music, current_time, is_key_down(TAP_PHASE_KEY));
+            if let Some(humanizer) = &mut humanizer {
+                humanizer.update(&mut music);
+            }
+            if let Some(tuning) = TUNING {
+                tuning.apply(&mut music);
+            }
+        }
+
+        if let (Some(practice), Some(practice_input)) = (&mut practice, &mut practice_input) {
+            let events = practice_input.poll(&timing, &music);
+            if let Some(player) = &mut player {
+                for event in &events {
+                    player.play_input(event);
+                }
+            }
+            practice.update(&music, timing.current_musical_time(&music), &events);
         }
 
         if EXPORT {
-            exporter.export_frame();
+            exporter.export_frame(|width, height| {
+                visualizer.draw(&mut Screen, timing.current_musical_time(&music), &music, practice.as_ref(), width, height)
+            });
+            clear_background(BLACK);
+            exporter.draw_preview();
+        } else {
+            visualizer.update(&timing, &music, practice.as_ref());
+        }
+        if let Some(player) = &mut player {
+            player.update(&timing, &music);
         }
 
         timing.update();
@@ -92,5 +189,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     exporter.finish();
 
+    if EXPORT && tap_phase.is_some() {
+        export_score(&music)?;
+    }
+
+    if let Some(practice) = &mut practice {
+        practice.finish();
+        let score = practice.score();
+        println!("practice score: {:.1}% ({score})", score.percentage());
+    }
+
+    Ok(())
+}
+
+fn export_score(music: &music::PianoPhase) -> Result<(), Box<dyn std::error::Error>> {
+    export::export_midi(music, CLICK_TRACK, MIDI_EXPORT_PATH)?;
+    export::export_timeline(music, TIMELINE_EXPORT_PATH)?;
+    export::export_webvtt(music, WEBVTT_EXPORT_PATH)?;
+    export::export_srt(music, SRT_EXPORT_PATH)?;
+    export::export_ffmetadata(music, FFMETADATA_EXPORT_PATH)?;
     Ok(())
 }
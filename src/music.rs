@@ -1,10 +1,13 @@
-use std::cmp::Ordering;
+use std::ops::RangeInclusive;
 
 use itertools::Itertools;
 use num_rational::{Ratio, Rational32};
 use num_traits::ToPrimitive;
 
-use crate::util::lerp;
+use crate::util::{lerp, remap};
+
+// the pitches that can be drawn on the treble staff without ledger lines, from E4 to F5
+pub const DRAWABLE_PITCHES: RangeInclusive<u8> = 64..=77;
 
 pub struct PianoPhase {
     // tempo is bpm for 16th note
@@ -12,6 +15,16 @@ pub struct PianoPhase {
 
     pub part1: Part,
     pub part2: Part,
+
+    // the landmarks of the score, in the time of part 1 because part 2 is out of sync with it while phasing
+    pub sections: Vec<Section>,
+    pub rehearsal_marks: Vec<RehearsalMark>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartNumber {
+    Part1,
+    Part2,
 }
 
 pub struct Part {
@@ -28,74 +41,169 @@ pub struct Segment {
     pub start_time: Rational32,
     pub end_time: Rational32,
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Pattern(pub Vec<Note>);
 impl Pattern {
     fn from_pitches(pitches: Vec<u8>) -> Pattern {
         let hands = std::iter::repeat(Hand::Left).interleave(std::iter::repeat(Hand::Right));
-        Pattern(pitches.into_iter().zip(hands).map(|(pitch, hand)| Note { pitch, hand }).collect())
+        Pattern(pitches.into_iter().zip(hands).map(|(pitch, hand)| Note { pitch, hand, accent: false }).collect())
     }
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Note {
     pub pitch: u8,
     pub hand: Hand,
+    // accented notes are played one dynamic level louder
+    pub accent: bool,
 }
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Hand {
     Left,
     Right,
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Dynamic {
-    Crescendo,
-    Decrescendo,
-    Flat,
+    Constant(DynamicLevel),
+    // a crescendo or decrescendo over the whole segment
+    Hairpin { from: DynamicLevel, to: DynamicLevel, curve: Curve },
+    // the part rests, so no notes are played at all
     Silent,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DynamicLevel {
+    Niente,
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+}
+// how a hairpin gets from one level to the other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    Linear,
+    // slow at the start and fast at the end
+    Exponential,
+    // slow at both ends and fast in the middle
+    SShaped,
+}
+
+// one of the three patterns of the piece
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: &'static str,
+    pub start_time: Rational32,
+}
+// the numbered modules of the published score, which start at the beginning of each steady passage
+#[derive(Clone, Copy, Debug)]
+pub struct RehearsalMark {
+    pub number: u32,
+    pub time: Rational32,
+}
 
 #[derive(Debug)]
 pub struct FlattenedNote {
     pub pitch: u8,
+    pub hand: Hand,
     pub time: Rational32,
     pub length: Rational32,
     pub volume: f32,
+    pub accent: bool,
+    pub performance: Performance,
 
     pub segment_index: usize,
     pub measure_number: usize,
 }
+// how a note is actually played, which is only different from how it is written when the performance is humanized or tuned
+#[derive(Clone, Copy, Debug)]
+pub struct Performance {
+    // in notes
+    pub time_offset: f32,
+    pub length_offset: f32,
+    pub volume: f32,
+    // how far the note is played from its equal tempered pitch, see tuning.rs
+    pub cents: f32,
+}
 
 impl PianoPhase {
     pub fn new(tempo: u16) -> Self {
-        let (part1, part2) = parts(false);
-        Self { tempo, part1, part2 }
+        let (part1, part2, sections, rehearsal_marks) = parts(false);
+        Self { tempo, part1, part2, sections, rehearsal_marks }
     }
     pub fn new_shortened(tempo: u16) -> Self {
-        let (part1, part2) = parts(true);
-        Self { tempo, part1, part2 }
+        let (part1, part2, sections, rehearsal_marks) = parts(true);
+        Self { tempo, part1, part2, sections, rehearsal_marks }
+    }
+
+    // accents the notes at these positions of every pattern, which the score leaves to the performers
+    // the written volumes are worked out again, so this has to be done before humanizing, tuning or phasing live
+    pub fn accent(&mut self, positions: &[usize]) {
+        for part in [&mut self.part1, &mut self.part2] {
+            for segment in &mut part.segments {
+                for (position, note) in segment.pattern.0.iter_mut().enumerate() {
+                    note.accent = positions.contains(&position);
+                }
+            }
+            for note_index in 0..part.flattened.len() {
+                let note = &part.flattened[note_index];
+                let segment = &part.segments[note.segment_index];
+                let position = note.position_in_pattern(part);
+                let accent = segment.pattern.0[position].accent;
+                let volume = written_volume(segment.dynamic, note.measure_number * segment.pattern.0.len() + position, segment.note_count(), accent);
+
+                let note = &mut part.flattened[note_index];
+                note.accent = accent;
+                note.volume = volume;
+                note.performance = Performance::exact(volume);
+            }
+        }
+    }
+
+    pub fn part(&self, part_number: PartNumber) -> &Part {
+        match part_number {
+            PartNumber::Part1 => &self.part1,
+            PartNumber::Part2 => &self.part2,
+        }
+    }
+
+    pub fn end_time(&self) -> Rational32 {
+        std::cmp::max(self.part1.end_time(), self.part2.end_time())
+    }
+
+    pub fn section_at(&self, time: f32) -> Option<&Section> {
+        self.sections.iter().rev().find(|section| section.start_time.to_f32().unwrap() <= time)
+    }
+    pub fn rehearsal_mark_at(&self, time: f32) -> Option<&RehearsalMark> {
+        self.rehearsal_marks.iter().rev().find(|mark| mark.time.to_f32().unwrap() <= time)
+    }
+
+    // how many notes part 2 is ahead of part 1 in their patterns, between 0 and the length of part 1's pattern
+    pub fn phase_offset(&self, time: f32) -> Option<f32> {
+        let position_in_pattern = |part: &Part| {
+            let segment = &part.segments[part.find_segment_for_time(time)?];
+            let notes_since_segment_start = (time - segment.start_time.to_f32().unwrap()) * segment.speed.to_f32().unwrap();
+            Some(notes_since_segment_start.rem_euclid(segment.pattern.0.len() as f32))
+        };
+        let part1_pattern_len = self.part1.segments[self.part1.find_segment_for_time(time)?].pattern.0.len() as f32;
+
+        let offset = position_in_pattern(&self.part2)? - position_in_pattern(&self.part1)?;
+        // snap to whole notes so that floating point error does not make an offset of 0 wrap around to the end of the pattern
+        let offset = if (offset - offset.round()).abs() < 1e-3 { offset.round() } else { offset };
+        Some(offset.rem_euclid(part1_pattern_len))
     }
 }
 impl Part {
+    pub fn end_time(&self) -> Rational32 {
+        self.segments.last().map_or(Ratio::ZERO, |segment| segment.end_time)
+    }
+
+    // the segment that is playing at the time, where a segment that ends at the time is already over
     pub fn find_segment_for_time(&self, time: f32) -> Option<usize> {
-        let result = self.segments.binary_search_by(|segment| {
-            match (segment.start_time.to_f32().unwrap().partial_cmp(&time), segment.end_time.to_f32().unwrap().partial_cmp(&time)) {
-                (Some(Ordering::Less), Some(Ordering::Less)) => Ordering::Less,
-                (Some(Ordering::Less), Some(Ordering::Equal)) => Ordering::Less,
-                (Some(Ordering::Less), Some(Ordering::Greater)) => Ordering::Equal,
-                (Some(Ordering::Equal), Some(Ordering::Less)) => panic!("segment starts later than it ends"),
-                (Some(Ordering::Equal), Some(Ordering::Equal)) => Ordering::Equal,
-                (Some(Ordering::Equal), Some(Ordering::Greater)) => Ordering::Equal,
-                (Some(Ordering::Greater), Some(Ordering::Less)) => panic!("segment starts later than it ends"),
-                (Some(Ordering::Greater), Some(Ordering::Equal)) => panic!("segment starts later than it ends"),
-                (Some(Ordering::Greater), Some(Ordering::Greater)) => Ordering::Greater,
-                _ => panic!("comparison resulted in None in find_segment_for_time"),
-            }
-        });
-        match result {
-            Ok(index) => Some(index),
-            Err(index) if index >= self.segments.len() => None,
-            Err(index) => panic!("searching for segment for time {time} resulted in index {index}, segments {:?}", self.segments),
-        }
+        let index = self.segments.partition_point(|segment| segment.end_time.to_f32().unwrap() <= time);
+        self.segments.get(index).filter(|segment| segment.start_time.to_f32().unwrap() <= time).map(|_| index)
     }
 
     pub fn find_note_range(&self, start: impl Fn(&FlattenedNote) -> bool, end: impl Fn(&FlattenedNote) -> bool) -> &[FlattenedNote] {
@@ -111,12 +219,17 @@ pub struct Measure {
     pub number: usize,
 }
 impl Segment {
+    pub fn note_count(&self) -> usize {
+        self.pattern.0.len() * self.repetitions as usize
+    }
     pub fn single_measure_duration(&self) -> Rational32 {
         Ratio::from_integer(self.pattern.0.len() as i32) / self.speed
     }
     pub fn find_measure(&self, time: f32) -> Measure {
-        let measure_number = ((time - self.start_time.to_f32().unwrap()) / self.single_measure_duration().to_f32().unwrap()).floor() as usize;
-        self.get_measure(measure_number)
+        let measures = (time - self.start_time.to_f32().unwrap()) / self.single_measure_duration().to_f32().unwrap();
+        // snap to whole measures so that floating point error does not put the start of a measure into the previous one
+        let measures = if (measures - measures.round()).abs() < 1e-4 { measures.round() } else { measures };
+        self.get_measure(measures.floor() as usize)
     }
     pub fn get_measure(&self, measure_number: usize) -> Measure {
         let start_time = Ratio::from_integer(measure_number as i32) * self.single_measure_duration() + self.start_time;
@@ -125,15 +238,127 @@ impl Segment {
     }
 }
 
+impl FlattenedNote {
+    // the volume compared to the loudest point of the segment the note is in, for fading notes in and out in the visualizer
+    pub fn relative_volume(&self, part: &Part) -> f32 {
+        relative_volume(self.volume, part.segments[self.segment_index].dynamic.peak_level())
+    }
+
+    // where the note is in the pattern of its segment
+    pub fn position_in_pattern(&self, part: &Part) -> usize {
+        let segment = &part.segments[self.segment_index];
+        ((self.time - segment.get_measure(self.measure_number).start_time) * segment.speed).to_integer() as usize
+    }
+
+    pub fn performed_start(&self) -> f32 {
+        self.time.to_f32().unwrap() + self.performance.time_offset
+    }
+    pub fn performed_end(&self) -> f32 {
+        (self.time + self.length).to_f32().unwrap() + self.performance.time_offset + self.performance.length_offset
+    }
+}
+
+impl Performance {
+    pub fn exact(volume: f32) -> Performance {
+        Performance { time_offset: 0.0, length_offset: 0.0, volume, cents: 0.0 }
+    }
+}
+
 impl Dynamic {
+    // the volume at t between 0 (the start of the segment) and 1 (the end of the segment)
     pub fn interpolate(&self, t: f32) -> f32 {
         match self {
-            Dynamic::Crescendo => lerp(0.0, 1.0, t),
-            Dynamic::Decrescendo => lerp(1.0, 0.0, t),
-            Dynamic::Flat => 1.0,
+            Dynamic::Constant(level) => level.volume(),
+            Dynamic::Hairpin { from, to, curve } => lerp(from.volume(), to.volume(), curve.shape(t)),
             Dynamic::Silent => 0.0,
         }
     }
+
+    pub fn peak_level(&self) -> Option<DynamicLevel> {
+        match *self {
+            Dynamic::Constant(level) => Some(level),
+            Dynamic::Hairpin { from, to, curve: _ } => Some(std::cmp::max(from, to)),
+            Dynamic::Silent => None,
+        }
+    }
+
+    // the volume relative to the loudest point of the dynamic, for fading things in and out in the visualizer
+    pub fn interpolate_relative(&self, t: f32) -> f32 {
+        relative_volume(self.interpolate(t), self.peak_level())
+    }
+}
+
+impl DynamicLevel {
+    const ALL: [DynamicLevel; 9] = [
+        DynamicLevel::Niente,
+        DynamicLevel::Ppp,
+        DynamicLevel::Pp,
+        DynamicLevel::P,
+        DynamicLevel::Mp,
+        DynamicLevel::Mf,
+        DynamicLevel::F,
+        DynamicLevel::Ff,
+        DynamicLevel::Fff,
+    ];
+
+    // the velocities that are played at each dynamic, up to the standard velocity that notation programs use for it
+    pub fn velocities(self) -> RangeInclusive<u8> {
+        match self {
+            DynamicLevel::Niente => 0..=0,
+            DynamicLevel::Ppp => 1..=16,
+            DynamicLevel::Pp => 17..=33,
+            DynamicLevel::P => 34..=49,
+            DynamicLevel::Mp => 50..=64,
+            DynamicLevel::Mf => 65..=80,
+            DynamicLevel::F => 81..=96,
+            DynamicLevel::Ff => 97..=112,
+            DynamicLevel::Fff => 113..=127,
+        }
+    }
+    // notes that are written at the level are played at the loudest velocity of it, and are only played quieter when humanized
+    pub fn velocity(self) -> u8 {
+        *self.velocities().end()
+    }
+    pub fn volume(self) -> f32 {
+        self.velocity() as f32 / 127.0
+    }
+    pub fn volumes(self) -> RangeInclusive<f32> {
+        *self.velocities().start() as f32 / 127.0..=self.volume()
+    }
+
+    // the level whose velocities the volume is played at
+    pub fn at_volume(volume: f32) -> DynamicLevel {
+        let velocity = volume_to_velocity(volume);
+        Self::ALL.into_iter().find(|level| level.velocities().contains(&velocity)).unwrap()
+    }
+    pub fn louder(self) -> DynamicLevel {
+        Self::ALL.get(self as usize + 1).copied().unwrap_or(self)
+    }
+}
+
+impl Curve {
+    // maps t between 0 and 1 to how far along the hairpin the volume is, also between 0 and 1
+    pub fn shape(self, t: f32) -> f32 {
+        const EXPONENT: f32 = 4.0;
+        match self {
+            Curve::Linear => t,
+            Curve::Exponential => (EXPONENT * t).exp_m1() / EXPONENT.exp_m1(),
+            Curve::SShaped => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// the midi velocity for a volume between 0 and 1
+pub fn volume_to_velocity(volume: f32) -> u8 {
+    (volume.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+// the volume compared to the loudest level of the dynamic it is in, between 0 and 1
+fn relative_volume(volume: f32, peak_level: Option<DynamicLevel>) -> f32 {
+    match peak_level {
+        Some(peak_level) if peak_level != DynamicLevel::Niente => (volume / peak_level.volume()).min(1.0),
+        _ => 0.0,
+    }
 }
 
 struct PartBuilder {
@@ -150,16 +375,20 @@ impl PartBuilder {
     fn add_segment(&mut self, pattern: Pattern, speed: Rational32, repetitions: u32, dynamic: Dynamic) {
         let segment_start_time = self.current_time;
         let segment_index = self.segments.len();
-        let total_number_of_notes = pattern.0.len() as i32 * repetitions as i32;
+        let total_number_of_notes = pattern.0.len() * repetitions as usize;
         let mut note_index = 0;
         for measure_number in 0..(repetitions as usize) {
             for note in &pattern.0 {
                 if dynamic != Dynamic::Silent {
+                    let volume = written_volume(dynamic, note_index, total_number_of_notes, note.accent);
                     self.flattened.push(FlattenedNote {
                         pitch: note.pitch,
+                        hand: note.hand,
                         time: self.current_time,
                         length: Ratio::ONE / speed,
-                        volume: dynamic.interpolate(note_index as f32 / total_number_of_notes as f32),
+                        volume,
+                        accent: note.accent,
+                        performance: Performance::exact(volume),
                         segment_index,
                         measure_number,
                     });
@@ -176,8 +405,46 @@ impl PartBuilder {
         Part { segments: self.segments, flattened: self.flattened }
     }
 }
-fn parts(shorten: bool) -> (Part, Part) {
+// the speed that moves part 2 one note ahead of part 1 over the repetitions of the pattern
+// speed = (pattern_len * repetitions) / (pattern_len * repetitions - 1)
+pub fn phase_speed(pattern_len: usize, repetitions: u32) -> Rational32 {
+    let notes = pattern_len as i32 * repetitions as i32;
+    Ratio::new(notes, notes - 1)
+}
+
+// accents are played one dynamic level louder, at the same place in the range of velocities of the louder level
+// silent notes stay silent, and the loudest level cannot be played any louder
+pub fn accented(volume: f32, accent: bool) -> f32 {
+    let level = DynamicLevel::at_volume(volume);
+    if accent && level != DynamicLevel::Niente {
+        let (volumes, louder_volumes) = (level.volumes(), level.louder().volumes());
+        remap(volume, *volumes.start(), *volumes.end(), *louder_volumes.start(), *louder_volumes.end())
+    } else {
+        volume
+    }
+}
+
+// the volume of the note at note_index of the notes of a segment, as written in the score
+fn written_volume(dynamic: Dynamic, note_index: usize, note_count: usize, accent: bool) -> f32 {
+    accented(dynamic.interpolate(note_index as f32 / note_count as f32), accent)
+}
+
+// the level that the whole piece is played at
+// the score only asks for the fades, so everything else is played as loud as it can be
+const LEVEL: DynamicLevel = DynamicLevel::Fff;
+const FLAT: Dynamic = Dynamic::Constant(LEVEL);
+const CRESCENDO: Dynamic = Dynamic::Hairpin { from: DynamicLevel::Niente, to: LEVEL, curve: Curve::Linear };
+const DECRESCENDO: Dynamic = Dynamic::Hairpin { from: LEVEL, to: DynamicLevel::Niente, curve: Curve::Linear };
+
+fn parts(shorten: bool) -> (Part, Part, Vec<Section>, Vec<RehearsalMark>) {
     let mut parts = (PartBuilder::new(), PartBuilder::new());
+    let mut sections = Vec::new();
+    let mut rehearsal_marks = Vec::new();
+
+    let mut section = |parts: &(PartBuilder, PartBuilder), name| sections.push(Section { name, start_time: parts.0.current_time });
+    let mut mark = |parts: &(PartBuilder, PartBuilder)| {
+        rehearsal_marks.push(RehearsalMark { number: rehearsal_marks.len() as u32 + 1, time: parts.0.current_time })
+    };
 
     let add_part_1 = |parts: &mut (PartBuilder, PartBuilder), pattern, speed, repetitions, dynamic| {
         parts.0.add_segment(pattern, speed, repetitions, dynamic);
@@ -189,102 +456,117 @@ fn parts(shorten: bool) -> (Part, Part) {
     let part_1_fade_in = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Crescendo);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, CRESCENDO);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, FLAT);
     };
     let part_1_fade_out = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Decrescendo);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, DECRESCENDO);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, FLAT);
     };
     let part_2_fade_in = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Crescendo);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, FLAT);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, CRESCENDO);
     };
     let part_2_fade_out = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Decrescendo);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, FLAT);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, DECRESCENDO);
     };
 
     let part_1_alone = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern.clone(), Ratio::ONE, repetitions, Dynamic::Flat);
+        add_part_1(parts, part1_pattern.clone(), Ratio::ONE, repetitions, FLAT);
         add_part_2(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Silent);
     };
     let part_2_alone = |parts: &mut (PartBuilder, PartBuilder), part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
         add_part_1(parts, part2_pattern.clone(), Ratio::ONE, repetitions, Dynamic::Silent);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, FLAT);
     };
 
     let parts_repeat = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
-        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, FLAT);
+        add_part_2(parts, part2_pattern, Ratio::ONE, repetitions, FLAT);
     };
 
     let part_2_phase = |parts: &mut (PartBuilder, PartBuilder), part1_pattern: Pattern, part2_pattern: Pattern, repetitions: u32| {
         let repetitions = if shorten { 1 } else { repetitions };
-        assert_eq!(part1_pattern.0.len(), part2_pattern.0.len());
-        let pattern_len = part1_pattern.0.len() as i32;
-        // speed multiplifier = (pattern_len * repetitions) / (pattern_len * repetitions - 1)
-        let speed_multiplier = Ratio::new(pattern_len * repetitions as i32, pattern_len * repetitions as i32 - 1);
+        let speed_multiplier = phase_speed(part1_pattern.0.len(), repetitions);
 
-        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, Dynamic::Flat);
-        add_part_2(parts, part2_pattern, speed_multiplier, repetitions, Dynamic::Flat);
+        add_part_1(parts, part1_pattern, Ratio::ONE, repetitions, FLAT);
+        add_part_2(parts, part2_pattern, speed_multiplier, repetitions, FLAT);
     };
 
     let part_2_catch_up = |parts: &mut (PartBuilder, PartBuilder), _: Pattern, part2_pattern: Pattern| {
-        add_part_2(parts, part2_pattern, Ratio::ONE, 1, Dynamic::Flat);
+        add_part_2(parts, part2_pattern, Ratio::ONE, 1, FLAT);
     };
 
+    // the phasing between two rehearsal marks belongs to the earlier one, the same as in the score
+    section(&parts, "Section I");
+    mark(&parts);
     (part_1_alone(&mut parts, pat1(), 8));
+    mark(&parts);
     (part_2_fade_in(&mut parts, pat1(), pat1(), 12));
 
     for _ in 0..11 {
         (part_2_phase(&mut parts, pat1(), pat1(), 8));
+        mark(&parts);
         (parts_repeat(&mut parts, pat1(), pat1(), 18));
     }
     (part_2_phase(&mut parts, pat1(), pat1(), 8));
+    mark(&parts);
     (part_2_catch_up(&mut parts, pat1(), pat1()));
     (part_2_fade_out(&mut parts, pat1(), pat1(), 8));
 
+    mark(&parts);
     (part_1_alone(&mut parts, pat1(), 6));
 
+    section(&parts, "Section II");
+    mark(&parts);
     (part_1_alone(&mut parts, pat2_1(), 6));
+    mark(&parts);
     (part_2_fade_in(&mut parts, pat2_1(), pat2_2(), 16));
 
     for _ in 0..7 {
         (part_2_phase(&mut parts, pat2_1(), pat2_2(), 12));
+        mark(&parts);
         (parts_repeat(&mut parts, pat2_1(), pat2_2(), 16));
     }
     (part_2_phase(&mut parts, pat2_1(), pat2_2(), 12));
+    mark(&parts);
     (part_2_catch_up(&mut parts, pat2_1(), pat2_2()));
     (part_1_fade_out(&mut parts, pat2_1(), pat2_2(), 16));
 
+    mark(&parts);
     (part_2_alone(&mut parts, pat2_2(), 8));
 
+    section(&parts, "Section III");
+    mark(&parts);
     (part_2_alone(&mut parts, pat2_into_3(), 1));
     (part_2_alone(&mut parts, pat3(), 16));
+    mark(&parts);
     (part_1_fade_in(&mut parts, pat3(), pat3(), 24));
 
     for _ in 0..3 {
         (part_2_phase(&mut parts, pat3(), pat3(), 18));
+        mark(&parts);
         (parts_repeat(&mut parts, pat3(), pat3(), 48));
     }
     part_2_phase(&mut parts, pat3(), pat3(), 18);
+    mark(&parts);
     part_2_catch_up(&mut parts, pat3(), pat3());
     parts_repeat(&mut parts, pat3(), pat3(), 48);
 
-    (parts.0.into_part(), parts.1.into_part())
+    (parts.0.into_part(), parts.1.into_part(), sections, rehearsal_marks)
 }
 
 fn pat1() -> Pattern {
@@ -306,3 +588,6 @@ fn pat2_into_3() -> Pattern {
 fn pat3() -> Pattern {
     Pattern::from_pitches(vec![69, 71, 74, 76])
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,224 @@
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+
+use super::*;
+use crate::util::Random;
+
+// the full piece, the shortened piece, and pieces with random pattern lengths and repetitions
+fn scores() -> Vec<PianoPhase> {
+    let mut scores = vec![PianoPhase::new(432), PianoPhase::new_shortened(432)];
+    let mut random = Random::new(&[44]);
+    scores.extend((0..200).map(|_| random_score(&mut random)));
+    scores
+}
+
+// a section of the piece with a random pattern and random repetitions: part 1 alone, part 2 fading in, a whole circle of phasing, and part 2
+// catching up
+// like in the piece, every phase of the circle has the same number of repetitions, since otherwise the notes would not fit into a midi file
+fn random_score(random: &mut Random) -> PianoPhase {
+    let mut between = |low: u64, high: u64| (low + random.next_u64() % (high - low + 1)) as u32;
+
+    let pattern_len = between(2, 16) as usize;
+    let pattern =
+        Pattern::from_pitches((0..pattern_len).map(|_| between(*DRAWABLE_PITCHES.start() as u64, *DRAWABLE_PITCHES.end() as u64) as u8).collect());
+    let (mut part1, mut part2) = (PartBuilder::new(), PartBuilder::new());
+
+    let repetitions = between(1, 8);
+    part1.add_segment(pattern.clone(), Ratio::ONE, repetitions, FLAT);
+    part2.add_segment(pattern.clone(), Ratio::ONE, repetitions, Dynamic::Silent);
+    let repetitions = between(1, 8);
+    part1.add_segment(pattern.clone(), Ratio::ONE, repetitions, FLAT);
+    part2.add_segment(pattern.clone(), Ratio::ONE, repetitions, CRESCENDO);
+
+    let phase_repetitions = between(1, 24);
+    for phase in 0..pattern_len {
+        part1.add_segment(pattern.clone(), Ratio::ONE, phase_repetitions, FLAT);
+        part2.add_segment(pattern.clone(), phase_speed(pattern_len, phase_repetitions), phase_repetitions, FLAT);
+        if phase + 1 < pattern_len {
+            let repetitions = between(1, 24);
+            part1.add_segment(pattern.clone(), Ratio::ONE, repetitions, FLAT);
+            part2.add_segment(pattern.clone(), Ratio::ONE, repetitions, FLAT);
+        }
+    }
+    part2.add_segment(pattern.clone(), Ratio::ONE, 1, FLAT);
+    let repetitions = between(1, 8);
+    part1.add_segment(pattern.clone(), Ratio::ONE, repetitions, FLAT);
+    part2.add_segment(pattern, Ratio::ONE, repetitions, FLAT);
+
+    PianoPhase { tempo: 432, part1: part1.into_part(), part2: part2.into_part(), sections: Vec::new(), rehearsal_marks: Vec::new() }
+}
+
+fn parts(music: &PianoPhase) -> [&Part; 2] {
+    [&music.part1, &music.part2]
+}
+
+#[test]
+fn scores_are_valid() {
+    for music in scores() {
+        if let Err(error) = music.validate() {
+            panic!("{error}");
+        }
+    }
+}
+
+#[test]
+fn segments_are_continuous() {
+    for music in scores() {
+        for part in parts(&music) {
+            assert_eq!(part.segments[0].start_time, Ratio::ZERO);
+            for (previous, segment) in part.segments.iter().zip(&part.segments[1..]) {
+                assert_eq!(segment.start_time, previous.end_time);
+            }
+            for segment in &part.segments {
+                assert_eq!(
+                    segment.end_time - segment.start_time,
+                    segment.single_measure_duration() * Ratio::from_integer(segment.repetitions as i32)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn phase_segments_end_one_note_ahead() {
+    for music in scores() {
+        for segment in music.part2.segments.iter().filter(|segment| segment.speed != Ratio::ONE) {
+            let pattern_len = segment.pattern.0.len() as f32;
+            let offset_before = music.phase_offset(segment.start_time.to_f32().unwrap()).unwrap();
+            let offset_after = music.phase_offset(segment.end_time.to_f32().unwrap()).unwrap();
+            assert_eq!((offset_after - offset_before).rem_euclid(pattern_len), 1.0, "phase segment starting at {}", segment.start_time);
+            // a whole number of notes apart, so that the parts play in rhythm again
+            assert_eq!(offset_after.fract(), 0.0);
+        }
+    }
+}
+
+#[test]
+fn parts_end_together() {
+    for music in scores() {
+        assert_eq!(music.part1.end_time(), music.part2.end_time());
+        assert_eq!(music.end_time(), music.part1.end_time());
+    }
+}
+
+#[test]
+fn find_segment_for_time_at_boundaries() {
+    for music in scores() {
+        for part in parts(&music) {
+            for (segment_index, segment) in part.segments.iter().enumerate() {
+                let (start, end) = (segment.start_time.to_f32().unwrap(), segment.end_time.to_f32().unwrap());
+                assert_eq!(part.find_segment_for_time(start), Some(segment_index));
+                assert_eq!(part.find_segment_for_time((start + end) / 2.0), Some(segment_index));
+                // a segment that ends at a time is already over, so the time belongs to the next segment
+                let next = if segment_index + 1 < part.segments.len() { Some(segment_index + 1) } else { None };
+                assert_eq!(part.find_segment_for_time(end), next);
+            }
+            assert_eq!(part.find_segment_for_time(-1.0), None);
+            assert_eq!(part.find_segment_for_time(part.end_time().to_f32().unwrap() + 1.0), None);
+        }
+    }
+}
+
+#[test]
+fn find_measure_at_boundaries() {
+    for music in scores() {
+        for part in parts(&music) {
+            for segment in &part.segments {
+                for measure_number in 0..segment.repetitions as usize {
+                    let measure = segment.get_measure(measure_number);
+                    assert_eq!(measure.end_time - measure.start_time, segment.single_measure_duration());
+                    assert_eq!(segment.find_measure(measure.start_time.to_f32().unwrap()).number, measure_number);
+                    let middle = ((measure.start_time + measure.end_time) / 2).to_f32().unwrap();
+                    assert_eq!(segment.find_measure(middle).number, measure_number);
+                }
+                assert_eq!(segment.get_measure(segment.repetitions as usize - 1).end_time, segment.end_time);
+            }
+        }
+    }
+}
+
+// find_note_range uses partition_point, which only works if the notes are sorted by both when they start and when they end
+#[test]
+fn flattened_notes_are_sorted() {
+    for music in scores() {
+        for part in parts(&music) {
+            for (previous, note) in part.flattened.iter().zip(&part.flattened[1..]) {
+                assert!(previous.time < note.time);
+                assert!(previous.time + previous.length <= note.time + note.length);
+                assert!(previous.time + previous.length <= note.time);
+                assert!((previous.segment_index, previous.measure_number) <= (note.segment_index, note.measure_number));
+            }
+            for note in &part.flattened {
+                let segment = &part.segments[note.segment_index];
+                let measure = segment.get_measure(note.measure_number);
+                assert!(measure.start_time <= note.time && note.time + note.length <= measure.end_time);
+            }
+        }
+    }
+}
+
+#[test]
+fn find_note_range_finds_the_notes_in_a_measure() {
+    for music in scores() {
+        for part in parts(&music) {
+            for (segment_index, segment) in part.segments.iter().enumerate() {
+                let measure = segment.get_measure(0);
+                let notes = part.find_note_range(|n| n.time < measure.start_time, |n| n.time < measure.end_time);
+                if segment.dynamic == Dynamic::Silent {
+                    assert!(notes.is_empty());
+                } else {
+                    assert_eq!(notes.len(), segment.pattern.0.len());
+                    assert!(notes.iter().all(|note| note.segment_index == segment_index && note.measure_number == 0));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn every_velocity_is_played_at_one_dynamic_level() {
+    for velocity in 0..=127 {
+        let levels: Vec<_> = DynamicLevel::ALL.into_iter().filter(|level| level.velocities().contains(&velocity)).collect();
+        assert_eq!(levels.len(), 1, "velocity {velocity} is played at {levels:?}");
+        assert_eq!(DynamicLevel::at_volume(velocity as f32 / 127.0), levels[0]);
+    }
+}
+
+#[test]
+fn steady_notes_are_played_at_full_velocity() {
+    let music = PianoPhase::new(432);
+    let steady = music.part1.flattened.iter().filter(|note| matches!(music.part1.segments[note.segment_index].dynamic, Dynamic::Constant(_)));
+    for note in steady {
+        assert_eq!(volume_to_velocity(note.volume), 127, "{note:?}");
+    }
+}
+
+#[test]
+fn accents_are_played_one_dynamic_level_louder() {
+    let written = PianoPhase::new_shortened(432);
+    let mut music = PianoPhase::new_shortened(432);
+    music.accent(&[0]);
+    for (written_part, part) in [(&written.part1, &music.part1), (&written.part2, &music.part2)] {
+        for segment in &part.segments {
+            assert!(segment.pattern.0.iter().map(|note| note.accent).eq((0..segment.pattern.0.len()).map(|position| position == 0)));
+        }
+        for (written_note, note) in written_part.flattened.iter().zip(&part.flattened) {
+            assert_eq!(note.accent, note.position_in_pattern(part) == 0, "{note:?}");
+            let written_level = DynamicLevel::at_volume(written_note.volume);
+            let level = DynamicLevel::at_volume(note.volume);
+            if !note.accent || written_level == DynamicLevel::Niente {
+                assert_eq!(note.volume, written_note.volume, "{note:?}");
+            } else {
+                assert_eq!(level, written_level.louder(), "{note:?} is not one level louder than {written_note:?}");
+            }
+        }
+    }
+
+    // taking the accents away again gives the written volumes back
+    music.accent(&[]);
+    for (written_note, note) in
+        written.part1.flattened.iter().chain(&written.part2.flattened).zip(music.part1.flattened.iter().chain(&music.part2.flattened))
+    {
+        assert_eq!((note.accent, note.volume, note.performance.volume), (false, written_note.volume, written_note.volume));
+    }
+}
@@ -1,60 +1,133 @@
 use std::{
     fs::File,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "audio-device")]
 use itertools::Itertools;
 use num_traits::ToPrimitive;
 use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+#[cfg(feature = "audio-device")]
 use tinyaudio::{run_output_device, BaseAudioOutputDevice, OutputDeviceParameters};
 
+#[cfg(feature = "audio-device")]
+use crate::click_track::{self, Click};
 use crate::{
-    music::{Part, PianoPhase},
+    click_track::ClickPulse,
+    error::{AssetError, AudioError},
+    humanize::MAX_TIME_OFFSET,
+    input::InputEvent,
+    music::{volume_to_velocity, Part, PartNumber, PianoPhase},
     timing::Timing,
-    util::remap,
+    tuning::pitch_bend,
 };
 
+// plays the music through the speakers
+// when there is no audio device (or the audio-device feature is off) it plays silently, so that everything else still works
 pub struct Player {
     synthesizer: Arc<Mutex<Synthesizer>>,
-    _device: Box<dyn BaseAudioOutputDevice>,
+    #[cfg(feature = "audio-device")]
+    _device: Option<Box<dyn BaseAudioOutputDevice>>,
+    muted_part: Option<PartNumber>,
+    // the pitch and the bend of the last note that each part started
+    last_notes: [Option<(u8, u16)>; 2],
+    // like the notes, the clicks are silent without an audio device
+    #[cfg_attr(not(feature = "audio-device"), allow(dead_code))]
+    click_pulse: Option<ClickPulse>,
+    // the clicks are mixed in after the synthesizer, so that they sound the same with any sound font
+    #[cfg(feature = "audio-device")]
+    clicks: Arc<Mutex<Vec<ClickVoice>>>,
+}
+
+// a short beep that dies away, which is pitched differently for each part and is higher and louder when accented
+#[cfg(feature = "audio-device")]
+struct ClickVoice {
+    frequency: f32,
+    volume: f32,
+    samples_played: usize,
 }
 
 const SAMPLE_RATE: i32 = 44100;
+const INPUT_CHANNEL: i32 = 2;
+// in seconds
+#[cfg(feature = "audio-device")]
+const CLICK_DECAY: f32 = 0.01;
+#[cfg(feature = "audio-device")]
+const CLICK_LENGTH: f32 = 0.05;
+#[cfg(feature = "audio-device")]
+const CLICK_VOLUME: f32 = 0.3;
+#[cfg(feature = "audio-device")]
+const ACCENTED_CLICK_VOLUME: f32 = 0.5;
 
 impl Player {
-    pub fn new() -> Result<Player, Box<dyn std::error::Error>> {
-        let mut sf2 = File::open("data/UprightPianoKW-small-SF2-20190703/UprightPianoKW-small-20190703.sf2")?;
-        let sound_font = Arc::new(SoundFont::new(&mut sf2)?);
+    pub fn new() -> Result<Player, AudioError> {
+        let sound_font_path = PathBuf::from("data/UprightPianoKW-small-SF2-20190703/UprightPianoKW-small-20190703.sf2");
+        let mut sf2 = File::open(&sound_font_path).map_err(|source| AssetError::Open { path: sound_font_path.clone(), source })?;
+        let sound_font = Arc::new(SoundFont::new(&mut sf2).map_err(|source| AssetError::InvalidSoundFont { path: sound_font_path, source })?);
 
         let settings = SynthesizerSettings::new(SAMPLE_RATE);
-        let synthesizer = Arc::new(Mutex::new(Synthesizer::new(&sound_font, &settings)?));
-
-        let params =
-            OutputDeviceParameters { channels_count: 2, sample_rate: SAMPLE_RATE as usize, channel_sample_count: SAMPLE_RATE as usize / 100 }; // dividing by 100 makes a maximum latency of 10ms
-        let _device = run_output_device(params, {
-            let mut left: Vec<f32> = vec![0_f32; params.channel_sample_count];
-            let mut right: Vec<f32> = vec![0_f32; params.channel_sample_count];
-            let synthesizer = Arc::clone(&synthesizer);
-            move |data| {
-                synthesizer.lock().unwrap().render(&mut left[..], &mut right[..]);
-                for (i, value) in left.iter().interleave(right.iter()).enumerate() {
-                    data[i] = *value;
-                }
+        let synthesizer = Arc::new(Mutex::new(Synthesizer::new(&sound_font, &settings).map_err(AudioError::Synthesizer)?));
+
+        #[cfg(feature = "audio-device")]
+        let clicks = Arc::new(Mutex::new(Vec::new()));
+        #[cfg(feature = "audio-device")]
+        let _device = match open_output_device(&synthesizer, &clicks) {
+            Ok(device) => Some(device),
+            Err(error) => {
+                println!("could not open the audio device, so nothing will be heard: {error}");
+                None
             }
+        };
+
+        Ok(Self {
+            synthesizer,
+            #[cfg(feature = "audio-device")]
+            _device,
+            muted_part: None,
+            last_notes: [None; 2],
+            click_pulse: None,
+            #[cfg(feature = "audio-device")]
+            clicks,
         })
-        .unwrap();
+    }
 
-        Ok(Self { synthesizer, _device })
+    // plays a click on every 8th note of the pulse, or turns the clicks off
+    pub fn set_click_track(&mut self, click_pulse: Option<ClickPulse>) {
+        self.click_pulse = click_pulse;
+    }
+
+    // stops a part from being played, for when someone else is playing it
+    pub fn mute_part(&mut self, part_number: PartNumber) {
+        self.muted_part = Some(part_number);
+    }
+
+    pub fn play_input(&mut self, event: &InputEvent) {
+        let mut synth = self.synthesizer.lock().unwrap();
+        match *event {
+            InputEvent::NoteOn { time: _, pitch, velocity } => synth.note_on(INPUT_CHANNEL, pitch as i32, velocity as i32),
+            InputEvent::NoteOff { time: _, pitch } => synth.note_off(INPUT_CHANNEL, pitch as i32),
+        }
     }
 
     pub fn update(&mut self, timing: &Timing, music: &PianoPhase) {
         let last_time = timing.last_musical_time(music);
         let this_time = timing.current_musical_time(music);
 
-        let update_part = |part: &Part, channel: i32| {
-            let notes_released =
-                part.find_note_range(|n| (n.time + n.length).to_f32().unwrap() < last_time, |n| (n.time + n.length).to_f32().unwrap() < this_time);
-            let notes_pressed = part.find_note_range(|n| n.time.to_f32().unwrap() < last_time, |n| n.time.to_f32().unwrap() < this_time);
+        let update_part = |part: &Part, channel: i32, last_note: &mut Option<(u8, u16)>| {
+            // the notes are searched for by when they are written, and then filtered by when they are actually played
+            let margin = 2.0 * MAX_TIME_OFFSET;
+            let notes_released = part
+                .find_note_range(
+                    |n| (n.time + n.length).to_f32().unwrap() < last_time - margin,
+                    |n| (n.time + n.length).to_f32().unwrap() < this_time + margin,
+                )
+                .iter()
+                .filter(|n| last_time <= n.performed_end() && n.performed_end() < this_time);
+            let notes_pressed = part
+                .find_note_range(|n| n.time.to_f32().unwrap() < last_time - margin, |n| n.time.to_f32().unwrap() < this_time + margin)
+                .iter()
+                .filter(|n| last_time <= n.performed_start() && n.performed_start() < this_time);
 
             {
                 let mut synth = self.synthesizer.lock().unwrap();
@@ -62,11 +135,92 @@ impl Player {
                     synth.note_off(channel, note.pitch as i32);
                 }
                 for note in notes_pressed {
-                    synth.note_on(channel, note.pitch as i32, remap(note.volume, 0.0, 1.0, 0.0, 127.0) as i32);
+                    // the whole channel is bent to the tuning of each note as it starts, see tuning::pitch_bend
+                    let bend = pitch_bend(note.performance.cents);
+                    if let Some((previous_pitch, previous_bend)) = *last_note {
+                        if previous_bend != bend {
+                            synth.note_off(channel, previous_pitch as i32);
+                        }
+                    }
+                    *last_note = Some((note.pitch, bend));
+                    let bend = bend as i32;
+                    synth.process_midi_message(channel, 0xe0, bend & 0x7f, bend >> 7);
+                    synth.note_on(channel, note.pitch as i32, volume_to_velocity(note.performance.volume) as i32);
                 }
             }
         };
-        update_part(&music.part1, 0);
-        update_part(&music.part2, 1);
+        if self.muted_part != Some(PartNumber::Part1) {
+            update_part(&music.part1, 0, &mut self.last_notes[0]);
+        }
+        if self.muted_part != Some(PartNumber::Part2) {
+            update_part(&music.part2, 1, &mut self.last_notes[1]);
+        }
+
+        // nothing plays the clicks when the device could not be opened, so they would only pile up
+        #[cfg(feature = "audio-device")]
+        if let (Some(click_pulse), Some(_)) = (self.click_pulse, &self._device) {
+            let new_clicks = click_track::clicks_between(music, click_pulse, last_time, this_time);
+            self.clicks.lock().unwrap().extend(new_clicks.iter().map(ClickVoice::new));
+        }
+    }
+}
+
+#[cfg(feature = "audio-device")]
+impl ClickVoice {
+    fn new(click: &Click) -> ClickVoice {
+        let frequency = match click.part_number {
+            PartNumber::Part1 => 1760.0,
+            PartNumber::Part2 => 2349.3,
+        };
+        let (frequency, volume) = if click.accent { (frequency * 2.0, ACCENTED_CLICK_VOLUME) } else { (frequency, CLICK_VOLUME) };
+        ClickVoice { frequency, volume, samples_played: 0 }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.samples_played as f32 >= CLICK_LENGTH * SAMPLE_RATE as f32
+    }
+
+    // adds the next samples of the click to the buffer
+    fn mix_into(&mut self, buffer: &mut [f32]) {
+        for sample in buffer {
+            let time = self.samples_played as f32 / SAMPLE_RATE as f32;
+            if time >= CLICK_LENGTH {
+                break;
+            }
+            *sample += self.volume * (time * self.frequency * std::f32::consts::TAU).sin() * (-time / CLICK_DECAY).exp();
+            self.samples_played += 1;
+        }
     }
 }
+
+#[cfg(feature = "audio-device")]
+fn open_output_device(
+    synthesizer: &Arc<Mutex<Synthesizer>>,
+    clicks: &Arc<Mutex<Vec<ClickVoice>>>,
+) -> Result<Box<dyn BaseAudioOutputDevice>, Box<dyn std::error::Error>> {
+    let params = OutputDeviceParameters { channels_count: 2, sample_rate: SAMPLE_RATE as usize, channel_sample_count: SAMPLE_RATE as usize / 100 }; // dividing by 100 makes a maximum latency of 10ms
+    run_output_device(params, {
+        let mut left: Vec<f32> = vec![0_f32; params.channel_sample_count];
+        let mut right: Vec<f32> = vec![0_f32; params.channel_sample_count];
+        let mut click = vec![0_f32; params.channel_sample_count];
+        let synthesizer = Arc::clone(synthesizer);
+        let clicks = Arc::clone(clicks);
+        move |data| {
+            synthesizer.lock().unwrap().render(&mut left[..], &mut right[..]);
+            {
+                let mut clicks = clicks.lock().unwrap();
+                click.fill(0.0);
+                for voice in clicks.iter_mut() {
+                    voice.mix_into(&mut click);
+                }
+                clicks.retain(|voice| !voice.is_finished());
+            }
+            for (channel, click) in left.iter_mut().zip(&click).chain(right.iter_mut().zip(&click)) {
+                *channel += click;
+            }
+            for (i, value) in left.iter().interleave(right.iter()).enumerate() {
+                data[i] = *value;
+            }
+        }
+    })
+}
@@ -0,0 +1,163 @@
+use num_traits::ToPrimitive;
+
+use crate::{
+    input::InputEvent,
+    music::{Part, PartNumber, PianoPhase},
+};
+
+// how far away (in notes) a played note can be from a note in the music for it to count as an attempt at playing that note
+const MATCH_WINDOW: f32 = 0.5;
+// how far away (in notes) a played note can be from the note that it is trying to play for it to count as on time
+const ON_TIME_WINDOW: f32 = 0.125;
+
+// judges notes played live against one of the parts of the music
+pub struct PracticeSession {
+    pub part_number: PartNumber,
+    pub played: Vec<PlayedNote>,
+
+    // for every note in the part, whether or not it is already taken care of (a played note was matched to it or it does not need to be played)
+    matched: Vec<bool>,
+    // every note before this index is too far in the past to be matched anymore
+    judged_up_to: usize,
+    missed: usize,
+}
+
+pub struct PlayedNote {
+    pub time: f32,
+    // the index into Part::flattened of the note that this note was trying to play
+    pub target: Option<usize>,
+    pub judgement: Judgement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Judgement {
+    OnTime,
+    Early,
+    Late,
+    WrongPitch,
+    // there was no note nearby that this note could have been trying to play
+    Extra,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub on_time: usize,
+    pub early: usize,
+    pub late: usize,
+    pub wrong_pitch: usize,
+    pub extra: usize,
+    pub missed: usize,
+}
+
+impl PracticeSession {
+    pub fn new(part_number: PartNumber, music: &PianoPhase) -> PracticeSession {
+        let mut session = PracticeSession { part_number, played: Vec::new(), matched: Vec::new(), judged_up_to: 0, missed: 0 };
+        session.add_new_notes(music.part(part_number));
+        session
+    }
+
+    // the part can grow while practicing (for example when part 2 is being phased live)
+    fn add_new_notes(&mut self, part: &Part) {
+        let known_notes = self.matched.len();
+        // notes at the very start of a crescendo have no volume, so they do not have to be played
+        self.matched.extend(part.flattened[known_notes..].iter().map(|note| note.volume == 0.0));
+    }
+
+    pub fn update(&mut self, music: &PianoPhase, current_time: f32, events: &[InputEvent]) {
+        let part = music.part(self.part_number);
+        self.add_new_notes(part);
+
+        for event in events {
+            if let InputEvent::NoteOn { time, pitch, velocity: _ } = *event {
+                self.judge_note(part, time, pitch);
+            }
+        }
+
+        while self.judged_up_to < part.flattened.len() && part.flattened[self.judged_up_to].time.to_f32().unwrap() + MATCH_WINDOW < current_time {
+            if !self.matched[self.judged_up_to] {
+                self.missed += 1;
+            }
+            self.judged_up_to += 1;
+        }
+    }
+
+    fn judge_note(&mut self, part: &Part, time: f32, pitch: u8) {
+        let candidates_start = part.flattened.partition_point(|n| n.time.to_f32().unwrap() < time - MATCH_WINDOW);
+        let candidates_end = part.flattened.partition_point(|n| n.time.to_f32().unwrap() <= time + MATCH_WINDOW);
+
+        let nearest_unmatched = |matching_pitch: bool| {
+            (candidates_start..candidates_end)
+                .filter(|&index| !self.matched[index] && (!matching_pitch || part.flattened[index].pitch == pitch))
+                .min_by(|&a, &b| {
+                    let distance = |index: usize| (part.flattened[index].time.to_f32().unwrap() - time).abs();
+                    distance(a).total_cmp(&distance(b))
+                })
+        };
+
+        let (target, judgement) = if let Some(target) = nearest_unmatched(true) {
+            let offset = time - part.flattened[target].time.to_f32().unwrap();
+            let judgement = if offset.abs() <= ON_TIME_WINDOW {
+                Judgement::OnTime
+            } else if offset < 0.0 {
+                Judgement::Early
+            } else {
+                Judgement::Late
+            };
+            (Some(target), judgement)
+        } else if let Some(target) = nearest_unmatched(false) {
+            (Some(target), Judgement::WrongPitch)
+        } else {
+            (None, Judgement::Extra)
+        };
+
+        if let Some(target) = target {
+            self.matched[target] = true;
+        }
+        self.played.push(PlayedNote { time, target, judgement });
+    }
+
+    // counts every note in the part that nothing was matched to as missed, for when the session is over
+    pub fn finish(&mut self) {
+        self.missed += self.matched[self.judged_up_to..].iter().filter(|matched| !**matched).count();
+        self.judged_up_to = self.matched.len();
+    }
+
+    pub fn score(&self) -> Score {
+        let mut score = Score { missed: self.missed, ..Default::default() };
+        for played in &self.played {
+            match played.judgement {
+                Judgement::OnTime => score.on_time += 1,
+                Judgement::Early => score.early += 1,
+                Judgement::Late => score.late += 1,
+                Judgement::WrongPitch => score.wrong_pitch += 1,
+                Judgement::Extra => score.extra += 1,
+            }
+        }
+        score
+    }
+}
+
+impl Score {
+    // notes that are early or late count for half, and extra notes count against the total
+    pub fn percentage(&self) -> f32 {
+        let attempts = self.on_time + self.early + self.late + self.wrong_pitch + self.extra + self.missed;
+        if attempts == 0 {
+            100.0
+        } else {
+            (self.on_time as f32 + (self.early + self.late) as f32 * 0.5) / attempts as f32 * 100.0
+        }
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} on time, {} early, {} late, {} wrong, {} extra, {} missed",
+            self.on_time, self.early, self.late, self.wrong_pitch, self.extra, self.missed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests;
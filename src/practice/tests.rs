@@ -0,0 +1,70 @@
+use super::*;
+
+// part 1 starts alone, with a note of the first pattern on every beat: 64 66 71 73 74 66 ...
+fn music() -> PianoPhase {
+    PianoPhase::new(432)
+}
+
+fn note_on(time: f32, pitch: u8) -> InputEvent {
+    InputEvent::NoteOn { time, pitch, velocity: 100 }
+}
+
+fn judgements(session: &PracticeSession) -> Vec<Judgement> {
+    session.played.iter().map(|played| played.judgement).collect()
+}
+
+#[test]
+fn notes_are_judged_by_how_far_they_are_from_the_nearest_note() {
+    let music = music();
+    let mut session = PracticeSession::new(PartNumber::Part1, &music);
+    let events = [
+        note_on(0.1, 64),
+        note_on(0.7, 66),
+        note_on(2.3, 71),
+        note_on(3.0, 60),
+        // the note at 4 was already played, so there is nothing left for the second one to try to play
+        note_on(4.0, 74),
+        note_on(4.0, 74),
+    ];
+    session.update(&music, 4.0, &events);
+    assert_eq!(
+        judgements(&session),
+        [Judgement::OnTime, Judgement::Early, Judgement::Late, Judgement::WrongPitch, Judgement::OnTime, Judgement::Extra]
+    );
+    assert_eq!(session.played.iter().map(|played| played.target).collect::<Vec<_>>(), [Some(0), Some(1), Some(2), Some(3), Some(4), None]);
+}
+
+#[test]
+fn notes_that_are_passed_without_being_played_are_missed() {
+    let music = music();
+    let mut session = PracticeSession::new(PartNumber::Part1, &music);
+    session.update(&music, 1.0, &[note_on(0.0, 64)]);
+    assert_eq!(session.score().missed, 0);
+    // the notes at 1 and 2 can no longer be played once the time is more than half a note past them
+    session.update(&music, 2.6, &[]);
+    assert_eq!(session.score().missed, 2);
+
+    // the rest of the part is missed when the session ends early
+    session.finish();
+    let notes = music.part1.flattened.iter().filter(|note| note.volume != 0.0).count();
+    assert_eq!(session.score(), Score { on_time: 1, missed: notes - 1, ..Default::default() });
+}
+
+#[test]
+fn notes_that_cannot_be_heard_do_not_have_to_be_played() {
+    let music = music();
+    let mut session = PracticeSession::new(PartNumber::Part2, &music);
+    // part 2 fades in, so its first note has no volume
+    let first_note = &music.part2.flattened[0];
+    assert_eq!(first_note.volume, 0.0);
+    session.update(&music, first_note.time.to_f32().unwrap() + 0.6, &[]);
+    assert_eq!(session.score().missed, 0);
+}
+
+#[test]
+fn early_and_late_notes_count_for_half() {
+    let score = Score { on_time: 2, early: 1, late: 1, wrong_pitch: 1, extra: 1, missed: 2 };
+    assert_eq!(score.percentage(), 3.0 / 8.0 * 100.0);
+    assert_eq!(Score::default().percentage(), 100.0);
+    assert_eq!(Score { missed: 4, ..Default::default() }.percentage(), 0.0);
+}
@@ -0,0 +1,132 @@
+use itertools::Itertools;
+use num_rational::Rational32;
+
+use crate::music::{PartNumber, PianoPhase};
+
+// the widest leap that is still heard as the same melodic line, in semitones
+// the patterns move between two registers a fourth or more apart, which are heard as separate lines
+const MAX_LEAP: u8 = 4;
+// how many notes a line needs before it is heard as a line of its own
+const MIN_LINE_NOTES: usize = 4;
+
+// the pattern that is heard when the two parts are combined
+pub struct Resultant {
+    pub start_time: Rational32,
+    pub end_time: Rational32,
+    pub events: Vec<ResultantEvent>,
+    // the notes of the combined pattern split into the melodic lines that they are heard as
+    pub lines: Vec<MelodicLine>,
+}
+
+// all of the notes that start at the same time
+pub struct ResultantEvent {
+    pub time: Rational32,
+    // sorted from highest to lowest
+    pub notes: Vec<ResultantNote>,
+}
+
+pub struct ResultantNote {
+    pub pitch: u8,
+    pub part_number: PartNumber,
+    pub volume: f32,
+    // the index of the line in Resultant::lines that the note belongs to
+    pub line: usize,
+}
+
+// notes that follow each other by small steps, which the ear groups together even when they come from different parts
+pub struct MelodicLine {
+    pub notes: Vec<LineNote>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineNote {
+    pub time: Rational32,
+    pub pitch: u8,
+    pub part_number: PartNumber,
+}
+
+impl Resultant {
+    // the combined notes of both parts during the measure of part 1 that is playing at the given time
+    pub fn at_time(music: &PianoPhase, time: f32) -> Option<Resultant> {
+        let segment = &music.part1.segments[music.part1.find_segment_for_time(time)?];
+        let measure = segment.find_measure(time);
+
+        let notes_in_measure =
+            |part_number: PartNumber| {
+                music.part(part_number).find_note_range(|n| n.time < measure.start_time, |n| n.time < measure.end_time).iter().map(move |n| {
+                    (n.time, ResultantNote { pitch: n.pitch, part_number, volume: n.relative_volume(music.part(part_number)), line: 0 })
+                })
+            };
+
+        let mut events: Vec<_> = notes_in_measure(PartNumber::Part1)
+            .merge_by(notes_in_measure(PartNumber::Part2), |(a_time, _), (b_time, _)| a_time <= b_time)
+            .chunk_by(|(time, _)| *time)
+            .into_iter()
+            .map(|(time, notes)| {
+                let mut notes: Vec<_> = notes.map(|(_, note)| note).collect();
+                notes.sort_by_key(|note| std::cmp::Reverse(note.pitch));
+                ResultantEvent { time, notes }
+            })
+            .collect();
+        let lines = find_lines(&mut events);
+
+        Some(Resultant { start_time: measure.start_time, end_time: measure.end_time, events, lines })
+    }
+
+    // the highest of the lines that the parts only make together, which is the one that stands out the most while the parts are out of sync
+    pub fn upper_emergent_line(&self) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.is_emergent())
+            .max_by(|(_, a), (_, b)| a.average_pitch().total_cmp(&b.average_pitch()))
+            .map(|(index, _)| index)
+    }
+}
+
+// each note continues the line that ended closest to its pitch, as long as that is not a leap and the line has no other note at the same time
+// the notes are taken from the highest down, so the higher notes get the first choice of line
+fn find_lines(events: &mut [ResultantEvent]) -> Vec<MelodicLine> {
+    let mut lines: Vec<MelodicLine> = Vec::new();
+    for event in events {
+        let lines_before_event = lines.len();
+        let mut continued = Vec::new();
+        for note in &mut event.notes {
+            let nearest_line = (0..lines_before_event)
+                .filter(|line| !continued.contains(line))
+                .map(|line| (line, lines[line].notes.last().unwrap().pitch.abs_diff(note.pitch)))
+                .filter(|&(_, leap)| leap <= MAX_LEAP)
+                .min_by_key(|&(_, leap)| leap);
+            note.line = match nearest_line {
+                Some((line, _)) => line,
+                None => {
+                    lines.push(MelodicLine { notes: Vec::new() });
+                    lines.len() - 1
+                }
+            };
+            continued.push(note.line);
+            lines[note.line].notes.push(LineNote { time: event.time, pitch: note.pitch, part_number: note.part_number });
+        }
+    }
+    lines
+}
+
+impl MelodicLine {
+    // a line that is long enough to be heard and that neither part plays on its own
+    pub fn is_emergent(&self) -> bool {
+        self.notes.len() >= MIN_LINE_NOTES && self.notes.iter().map(|note| note.part_number).all_equal_value().is_err()
+    }
+
+    fn average_pitch(&self) -> f32 {
+        self.notes.iter().map(|note| note.pitch as f32).sum::<f32>() / self.notes.len() as f32
+    }
+}
+
+impl ResultantEvent {
+    pub fn is_unison(&self) -> bool {
+        self.notes.iter().tuple_windows().any(|(a, b)| a.pitch == b.pitch)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,92 @@
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+
+use super::*;
+use crate::music::{Dynamic, Pattern};
+
+// the first measure of every steady passage where both parts play, with how many notes part 2 is ahead and the patterns of both parts
+fn steady_measures(music: &PianoPhase) -> Vec<(f32, usize, &Pattern, &Pattern)> {
+    music
+        .part1
+        .segments
+        .iter()
+        .filter_map(|part1_segment| {
+            let time = part1_segment.start_time.to_f32().unwrap();
+            let part2_segment = &music.part2.segments[music.part2.find_segment_for_time(time)?];
+            let both_play = part1_segment.dynamic != Dynamic::Silent && part2_segment.dynamic != Dynamic::Silent;
+            let steady = part2_segment.speed == Ratio::ONE && part1_segment.pattern.0.len() == part2_segment.pattern.0.len();
+            let offset = music.phase_offset(time)?;
+            (both_play && steady && offset.fract() == 0.0).then_some((time, offset as usize, &part1_segment.pattern, &part2_segment.pattern))
+        })
+        .collect()
+}
+
+#[test]
+fn the_parts_are_combined_at_each_offset() {
+    let music = PianoPhase::new(432);
+    let measures = steady_measures(&music);
+    // every offset of the first pattern is heard
+    assert!((0..12).all(|offset| measures.iter().any(|&(_, measure_offset, part1, _)| measure_offset == offset && part1.0.len() == 12)));
+
+    for (time, offset, part1_pattern, part2_pattern) in measures {
+        let resultant = Resultant::at_time(&music, time + 0.5).unwrap();
+        assert_eq!(resultant.events.len(), part1_pattern.0.len());
+        for (index, event) in resultant.events.iter().enumerate() {
+            let part1_pitch = part1_pattern.0[index].pitch;
+            let part2_pitch = part2_pattern.0[(index + offset) % part2_pattern.0.len()].pitch;
+            let pitches: Vec<_> = event.notes.iter().map(|note| note.pitch).collect();
+            assert_eq!(pitches, [part1_pitch.max(part2_pitch), part1_pitch.min(part2_pitch)], "at offset {offset}");
+            assert_eq!(event.is_unison(), part1_pitch == part2_pitch, "at offset {offset}");
+        }
+    }
+}
+
+#[test]
+fn only_part_1_is_heard_while_it_plays_alone() {
+    let music = PianoPhase::new(432);
+    let resultant = Resultant::at_time(&music, 0.5).unwrap();
+    assert!(resultant.events.iter().all(|event| event.notes.len() == 1 && event.notes[0].part_number == PartNumber::Part1));
+    assert!(!resultant.events.iter().any(ResultantEvent::is_unison));
+    assert_eq!(resultant.upper_emergent_line(), None);
+}
+
+#[test]
+fn lines_move_by_steps_and_take_one_note_at_a_time() {
+    let music = PianoPhase::new(432);
+    for (time, offset, _, _) in steady_measures(&music) {
+        let resultant = Resultant::at_time(&music, time + 0.5).unwrap();
+        for line in &resultant.lines {
+            for (a, b) in line.notes.iter().tuple_windows() {
+                assert!(a.time < b.time, "two notes at once in a line at offset {offset}");
+                assert!(a.pitch.abs_diff(b.pitch) <= MAX_LEAP, "a leap in a line at offset {offset}");
+            }
+        }
+        // every note is in the line that it points to
+        for event in &resultant.events {
+            for note in &event.notes {
+                let line_note = LineNote { time: event.time, pitch: note.pitch, part_number: note.part_number };
+                assert!(resultant.lines[note.line].notes.contains(&line_note));
+            }
+        }
+        let notes = resultant.events.iter().map(|event| event.notes.len()).sum::<usize>();
+        assert_eq!(resultant.lines.iter().map(|line| line.notes.len()).sum::<usize>(), notes);
+    }
+}
+
+#[test]
+fn lines_only_emerge_when_the_parts_are_out_of_sync() {
+    let music = PianoPhase::new(432);
+    for (time, offset, part1_pattern, part2_pattern) in steady_measures(&music) {
+        let resultant = Resultant::at_time(&music, time + 0.5).unwrap();
+        if offset == 0 && part1_pattern == part2_pattern {
+            // in unison every line is one part doubling the other
+            assert_eq!(resultant.upper_emergent_line(), None);
+        } else if part1_pattern.0.len() == 12 {
+            // the first pattern moves between e-f# and b-c#-d, so the upper line that the parts make together stays up there
+            let upper_line = &resultant.lines[resultant.upper_emergent_line().unwrap()];
+            assert!(upper_line.notes.iter().all(|note| (71..=74).contains(&note.pitch)), "at offset {offset}");
+            assert!(upper_line.notes.iter().any(|note| note.part_number == PartNumber::Part1));
+            assert!(upper_line.notes.iter().any(|note| note.part_number == PartNumber::Part2));
+        }
+    }
+}
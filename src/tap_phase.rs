@@ -0,0 +1,94 @@
+use num_rational::{Ratio, Rational32};
+use num_traits::ToPrimitive;
+
+use crate::{
+    music::{accented, phase_speed, Dynamic, FlattenedNote, Part, Performance, PianoPhase, Segment},
+    util::remap,
+};
+
+// how many measures of phasing it takes for part 2 to get one note ahead of part 1
+const MEASURES_PER_NOTE: u32 = 4;
+// how far ahead of the current time (in notes) the next measure of part 2 is decided
+const LOOKAHEAD: f32 = 1.0;
+
+// lets part 2 be phased live by holding down a key instead of following the phasing written in the score
+// part 2 is generated one measure at a time, and a measure phases if the key is held down when it is generated
+pub struct TapPhase {
+    // part 2 as it is written in the score, which is where the patterns and dynamics come from
+    score_part2: Part,
+    // how many notes part 2 is ahead of part 1
+    pub offset: Rational32,
+}
+
+impl TapPhase {
+    pub fn new(music: &mut PianoPhase) -> TapPhase {
+        let score_part2 = std::mem::replace(&mut music.part2, Part { segments: Vec::new(), flattened: Vec::new() });
+        TapPhase { score_part2, offset: Ratio::ZERO }
+    }
+
+    pub fn update(&mut self, music: &mut PianoPhase, current_time: f32, phase_key_held: bool) {
+        while music.part2.end_time().to_f32().unwrap() <= current_time + LOOKAHEAD {
+            if !self.add_measure(&mut music.part2, phase_key_held) {
+                break;
+            }
+        }
+    }
+
+    // returns false if there is nothing left in the score to add
+    fn add_measure(&mut self, part2: &mut Part, phase_key_held: bool) -> bool {
+        let start_time = part2.end_time();
+        let Some(score_segment_index) = self.score_part2.find_segment_for_time(start_time.to_f32().unwrap()) else {
+            return false;
+        };
+        let score_segment = &self.score_part2.segments[score_segment_index];
+
+        // once the key is released, part 2 keeps phasing until it lines up with part 1 again
+        let phasing = phase_key_held || !self.offset.is_integer();
+        let pattern_len = score_segment.pattern.0.len() as i32;
+        // the same speed as part_2_phase uses, so that every measure of phasing moves part 2 ahead by 1 / MEASURES_PER_NOTE notes
+        let speed = if phasing { phase_speed(pattern_len as usize, MEASURES_PER_NOTE) } else { Ratio::ONE };
+        // the dynamics are taken from the score note by note, so the generated segments themselves do not have a hairpin
+        let dynamic = score_segment.dynamic.peak_level().map_or(Dynamic::Silent, Dynamic::Constant);
+
+        let continues_last_segment =
+            part2.segments.last().is_some_and(|last| last.speed == speed && last.dynamic == dynamic && last.pattern == score_segment.pattern);
+        if !continues_last_segment {
+            part2.segments.push(Segment { pattern: score_segment.pattern.clone(), speed, repetitions: 0, dynamic, start_time, end_time: start_time });
+        }
+
+        let segment_index = part2.segments.len() - 1;
+        let segment = &mut part2.segments[segment_index];
+        let measure_number = segment.repetitions as usize;
+        let mut time = start_time;
+        for note in &segment.pattern.0 {
+            if dynamic != Dynamic::Silent {
+                let offset_in_score_segment =
+                    remap(time.to_f32().unwrap(), score_segment.start_time.to_f32().unwrap(), score_segment.end_time.to_f32().unwrap(), 0.0, 1.0);
+                let volume = accented(score_segment.dynamic.interpolate(offset_in_score_segment.clamp(0.0, 1.0)), note.accent);
+                part2.flattened.push(FlattenedNote {
+                    pitch: note.pitch,
+                    hand: note.hand,
+                    time,
+                    length: Ratio::ONE / speed,
+                    volume,
+                    accent: note.accent,
+                    performance: Performance::exact(volume),
+                    segment_index,
+                    measure_number,
+                });
+            }
+            time += Ratio::ONE / speed;
+        }
+        segment.repetitions += 1;
+        segment.end_time = time;
+
+        if phasing {
+            self.offset += Ratio::from_integer(pattern_len) - Ratio::from_integer(pattern_len) / speed;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests;
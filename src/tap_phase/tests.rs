@@ -0,0 +1,65 @@
+use super::*;
+
+fn assert_continuous(part: &Part) {
+    assert_eq!(part.segments[0].start_time, Ratio::ZERO);
+    for (previous, segment) in part.segments.iter().zip(&part.segments[1..]) {
+        assert_eq!(segment.start_time, previous.end_time);
+    }
+    for segment in &part.segments {
+        assert_eq!(segment.end_time - segment.start_time, segment.single_measure_duration() * Ratio::from_integer(segment.repetitions as i32));
+    }
+}
+
+#[test]
+fn holding_the_key_moves_part_2_ahead_a_little_every_measure() {
+    for measures in 1..=2 * MEASURES_PER_NOTE {
+        let mut music = PianoPhase::new(432);
+        let mut tap_phase = TapPhase::new(&mut music);
+        for _ in 0..measures {
+            assert!(tap_phase.add_measure(&mut music.part2, true));
+        }
+        assert_eq!(tap_phase.offset, Ratio::new(measures as i32, MEASURES_PER_NOTE as i32));
+        // part 1 plays a note on every beat, so part 2 is ahead by how many more notes it has played
+        let notes_played = Ratio::from_integer((measures as usize * music.part2.segments[0].pattern.0.len()) as i32);
+        assert_eq!(notes_played - music.part2.end_time(), tap_phase.offset);
+        assert_continuous(&music.part2);
+    }
+}
+
+#[test]
+fn releasing_the_key_phases_until_the_parts_line_up_again() {
+    let mut music = PianoPhase::new(432);
+    let mut tap_phase = TapPhase::new(&mut music);
+    for _ in 0..MEASURES_PER_NOTE + 1 {
+        tap_phase.add_measure(&mut music.part2, true);
+    }
+    let measures_to_line_up = MEASURES_PER_NOTE - 1;
+    for _ in 0..measures_to_line_up + 3 {
+        tap_phase.add_measure(&mut music.part2, false);
+    }
+    assert_eq!(tap_phase.offset, Ratio::from_integer(2));
+
+    // the key was held into the second note, so it keeps phasing until part 2 is two notes ahead, and then plays steadily
+    // (the steady measures are split into more than one segment where the dynamic of the score changes)
+    let (phasing, steady): (Vec<_>, Vec<_>) = music.part2.segments.iter().partition(|segment| segment.speed != Ratio::ONE);
+    assert!(music.part2.segments[..phasing.len()].iter().all(|segment| segment.speed != Ratio::ONE));
+    assert_eq!(phasing.iter().map(|segment| segment.repetitions).sum::<u32>(), 2 * MEASURES_PER_NOTE);
+    assert_eq!(steady.iter().map(|segment| segment.repetitions).sum::<u32>(), 3);
+    assert_continuous(&music.part2);
+    // part 2 is on the beat of part 1 again
+    assert!(music.part2.end_time().is_integer());
+}
+
+#[test]
+fn part_2_is_generated_just_ahead_of_the_time() {
+    let mut music = PianoPhase::new(432);
+    let mut tap_phase = TapPhase::new(&mut music);
+    for time in 0..200 {
+        let time = time as f32;
+        tap_phase.update(&mut music, time, (50.0..100.0).contains(&time));
+        assert!(music.part2.end_time().to_f32().unwrap() > time + LOOKAHEAD);
+    }
+    assert_continuous(&music.part2);
+    assert!(tap_phase.offset.is_integer());
+    assert!(tap_phase.offset > Ratio::ZERO);
+}
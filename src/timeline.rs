@@ -0,0 +1,198 @@
+use num_rational::Rational32;
+use serde::Serialize;
+
+use crate::music::{Curve, Dynamic, DynamicLevel, FlattenedNote, Hand, Part, Performance, PianoPhase, Segment};
+
+// the computed score as it is written to json, for other tools to read
+// this is kept separate from the types in music.rs so that the format stays the same when those change
+// the format is documented in docs/timeline.schema.json, and VERSION should be increased whenever it changes in an incompatible way
+const VERSION: u32 = 1;
+
+// times and speeds are exact fractions, written as [numerator, denominator]
+type Fraction = [i32; 2];
+
+#[derive(Serialize)]
+pub struct Timeline {
+    version: u32,
+    tempo: u16,
+    end_time: Fraction,
+    sections: Vec<TimelineSection>,
+    rehearsal_marks: Vec<TimelineRehearsalMark>,
+    parts: Vec<TimelinePart>,
+}
+
+#[derive(Serialize)]
+struct TimelineSection {
+    name: &'static str,
+    start_time: Fraction,
+}
+
+#[derive(Serialize)]
+struct TimelineRehearsalMark {
+    number: u32,
+    time: Fraction,
+}
+
+#[derive(Serialize)]
+struct TimelinePart {
+    segments: Vec<TimelineSegment>,
+    notes: Vec<TimelineNote>,
+}
+
+#[derive(Serialize)]
+struct TimelineSegment {
+    pattern: Vec<TimelinePatternNote>,
+    speed: Fraction,
+    repetitions: u32,
+    dynamic: TimelineDynamic,
+    start_time: Fraction,
+    end_time: Fraction,
+}
+
+// written as {"type": "constant", "level": "mf"}, {"type": "hairpin", "from": "n", "to": "mf", "curve": "linear"} or {"type": "silent"}
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TimelineDynamic {
+    Constant { level: &'static str },
+    Hairpin { from: &'static str, to: &'static str, curve: &'static str },
+    Silent,
+}
+
+#[derive(Serialize)]
+struct TimelinePatternNote {
+    pitch: u8,
+    hand: &'static str,
+    accent: bool,
+}
+
+#[derive(Serialize)]
+struct TimelineNote {
+    pitch: u8,
+    hand: &'static str,
+    time: Fraction,
+    length: Fraction,
+    volume: f32,
+    accent: bool,
+    performance: TimelinePerformance,
+    segment_index: usize,
+    measure_number: usize,
+}
+
+#[derive(Serialize)]
+struct TimelinePerformance {
+    time_offset: f32,
+    length_offset: f32,
+    volume: f32,
+    cents: f32,
+}
+
+impl Timeline {
+    pub fn new(music: &PianoPhase) -> Timeline {
+        Timeline {
+            version: VERSION,
+            tempo: music.tempo,
+            end_time: fraction(music.end_time()),
+            sections: music.sections.iter().map(|section| TimelineSection { name: section.name, start_time: fraction(section.start_time) }).collect(),
+            rehearsal_marks: music
+                .rehearsal_marks
+                .iter()
+                .map(|rehearsal_mark| TimelineRehearsalMark { number: rehearsal_mark.number, time: fraction(rehearsal_mark.time) })
+                .collect(),
+            parts: [&music.part1, &music.part2].into_iter().map(TimelinePart::new).collect(),
+        }
+    }
+}
+
+impl TimelinePart {
+    fn new(part: &Part) -> TimelinePart {
+        TimelinePart {
+            segments: part.segments.iter().map(TimelineSegment::new).collect(),
+            notes: part.flattened.iter().map(TimelineNote::new).collect(),
+        }
+    }
+}
+
+impl TimelineSegment {
+    fn new(segment: &Segment) -> TimelineSegment {
+        TimelineSegment {
+            pattern: segment
+                .pattern
+                .0
+                .iter()
+                .map(|note| TimelinePatternNote { pitch: note.pitch, hand: hand_name(note.hand), accent: note.accent })
+                .collect(),
+            speed: fraction(segment.speed),
+            repetitions: segment.repetitions,
+            dynamic: match segment.dynamic {
+                Dynamic::Constant(level) => TimelineDynamic::Constant { level: level_name(level) },
+                Dynamic::Hairpin { from, to, curve } => TimelineDynamic::Hairpin {
+                    from: level_name(from),
+                    to: level_name(to),
+                    curve: match curve {
+                        Curve::Linear => "linear",
+                        Curve::Exponential => "exponential",
+                        Curve::SShaped => "s_curve",
+                    },
+                },
+                Dynamic::Silent => TimelineDynamic::Silent,
+            },
+            start_time: fraction(segment.start_time),
+            end_time: fraction(segment.end_time),
+        }
+    }
+}
+
+impl TimelineNote {
+    fn new(note: &FlattenedNote) -> TimelineNote {
+        TimelineNote {
+            pitch: note.pitch,
+            hand: hand_name(note.hand),
+            time: fraction(note.time),
+            length: fraction(note.length),
+            volume: note.volume,
+            accent: note.accent,
+            performance: TimelinePerformance::new(&note.performance),
+            segment_index: note.segment_index,
+            measure_number: note.measure_number,
+        }
+    }
+}
+
+impl TimelinePerformance {
+    fn new(performance: &Performance) -> TimelinePerformance {
+        TimelinePerformance {
+            time_offset: performance.time_offset,
+            length_offset: performance.length_offset,
+            volume: performance.volume,
+            cents: performance.cents,
+        }
+    }
+}
+
+fn hand_name(hand: Hand) -> &'static str {
+    match hand {
+        Hand::Left => "left",
+        Hand::Right => "right",
+    }
+}
+
+fn level_name(level: DynamicLevel) -> &'static str {
+    match level {
+        DynamicLevel::Niente => "n",
+        DynamicLevel::Ppp => "ppp",
+        DynamicLevel::Pp => "pp",
+        DynamicLevel::P => "p",
+        DynamicLevel::Mp => "mp",
+        DynamicLevel::Mf => "mf",
+        DynamicLevel::F => "f",
+        DynamicLevel::Ff => "ff",
+        DynamicLevel::Fff => "fff",
+    }
+}
+
+fn fraction(value: Rational32) -> Fraction {
+    [*value.numer(), *value.denom()]
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,132 @@
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use super::*;
+use crate::{
+    humanize::{Humanization, Humanizer},
+    tuning::Tuning,
+};
+
+const SCHEMA_PATH: &str = "docs/timeline.schema.json";
+
+fn schema() -> Value {
+    let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_PATH);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// the shortened piece with every part of the format filled in: accents, humanized performances and tuning
+fn timeline() -> Value {
+    let mut music = PianoPhase::new_shortened(432);
+    music.accent(&[0]);
+    Humanizer::new(Humanization::NATURAL).update(&mut music);
+    Tuning::TWO_PIANOS.apply(&mut music);
+    serde_json::to_value(Timeline::new(&music)).unwrap()
+}
+
+// checks the value against the parts of json schema that docs/timeline.schema.json uses, and returns where it does not match
+fn schema_errors(value: &Value, schema: &Value, root: &Value, path: &str) -> Vec<String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let definition = root.pointer(reference.trim_start_matches('#')).unwrap_or_else(|| panic!("{reference} is not in the schema"));
+        return schema_errors(value, definition, root, path);
+    }
+    let mut errors = Vec::new();
+    let mut error = |message: String| errors.push(format!("{path}: {message}"));
+
+    if let Some(expected) = schema["type"].as_str() {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            _ => panic!("the schema uses the type {expected}, which is not checked"),
+        };
+        if !matches {
+            error(format!("{value} is not of type {expected}"));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            error(format!("{value} is not {expected}"));
+        }
+    }
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            error(format!("{value} is not one of {options:?}"));
+        }
+    }
+    if let (Some(minimum), Some(number)) = (schema["minimum"].as_f64(), value.as_f64()) {
+        if number < minimum {
+            error(format!("{number} is less than {minimum}"));
+        }
+    }
+    if let (Some(maximum), Some(number)) = (schema["maximum"].as_f64(), value.as_f64()) {
+        if number > maximum {
+            error(format!("{number} is more than {maximum}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for required in schema["required"].as_array().into_iter().flatten() {
+            if !object.contains_key(required.as_str().unwrap()) {
+                error(format!("{required} is missing"));
+            }
+        }
+        for (key, property) in object {
+            match schema["properties"].get(key) {
+                Some(property_schema) => errors.extend(schema_errors(property, property_schema, root, &format!("{path}/{key}"))),
+                None if schema["additionalProperties"] == false => errors.push(format!("{path}: {key} is not in the schema")),
+                None => {}
+            }
+        }
+    }
+    if let Some(array) = value.as_array() {
+        let length = array.len() as u64;
+        if schema["minItems"].as_u64().is_some_and(|min_items| length < min_items)
+            || schema["maxItems"].as_u64().is_some_and(|max_items| length > max_items)
+        {
+            errors.push(format!("{path}: {length} items is not between {} and {}", schema["minItems"], schema["maxItems"]));
+        }
+        let prefix_items = schema["prefixItems"].as_array().map_or(&[][..], |prefix_items| &prefix_items[..]);
+        for (i, item) in array.iter().enumerate() {
+            let item_schema = prefix_items.get(i).or(schema.get("items"));
+            if let Some(item_schema) = item_schema {
+                errors.extend(schema_errors(item, item_schema, root, &format!("{path}/{i}")));
+            }
+        }
+    }
+    if let Some(options) = schema["oneOf"].as_array() {
+        let matching = options.iter().filter(|option| schema_errors(value, option, root, path).is_empty()).count();
+        if matching != 1 {
+            errors.push(format!("{path}: {value} matches {matching} of the options instead of one"));
+        }
+    }
+    errors
+}
+
+#[test]
+fn timeline_matches_the_schema() {
+    let schema = schema();
+    let errors = schema_errors(&timeline(), &schema, &schema, "");
+    assert!(errors.is_empty(), "the timeline does not match {SCHEMA_PATH}:\n{}", errors.iter().take(20).join("\n"));
+}
+
+#[test]
+fn the_schema_finds_timelines_that_do_not_match_it() {
+    let schema = schema();
+    let broken = |break_timeline: &dyn Fn(&mut Value)| {
+        let mut timeline = timeline();
+        break_timeline(&mut timeline);
+        schema_errors(&timeline, &schema, &schema, "")
+    };
+
+    assert!(!broken(&|timeline| timeline["version"] = json!(VERSION + 1)).is_empty(), "a different version matches");
+    assert!(!broken(&|timeline| timeline["parts"][1]["notes"][0].as_object_mut().unwrap().clear()).is_empty(), "a note without fields matches");
+    assert!(!broken(&|timeline| timeline["parts"][0]["notes"][0]["performance"]["pedal"] = json!(true)).is_empty(), "an unknown field matches");
+    assert!(
+        !broken(&|timeline| timeline["parts"][0]["segments"][0]["dynamic"]["type"] = json!("sforzando")).is_empty(),
+        "an unknown dynamic matches"
+    );
+    assert!(!broken(&|timeline| timeline["end_time"] = json!([1, 0])).is_empty(), "a fraction with a denominator of 0 matches");
+}
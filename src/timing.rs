@@ -54,6 +54,21 @@ impl Timing {
         music.tempo as f32 * self.time.as_secs_f32() / 60.0
     }
 
+    // the musical time at some instant close to the current time, for timestamping events that happened between frames
+    pub fn musical_time_at_instant(&self, instant: Instant, music: &PianoPhase) -> f32 {
+        let time = match self.last_instant {
+            Some(last_instant) if !self.stopped && self.constant_fps.is_none() => {
+                if instant >= last_instant {
+                    self.time + instant.duration_since(last_instant)
+                } else {
+                    self.time.saturating_sub(last_instant.duration_since(instant))
+                }
+            }
+            _ => self.time,
+        };
+        music.tempo as f32 * time.as_secs_f32() / 60.0
+    }
+
     pub fn toggle_stopped(&mut self) {
         self.stopped = !self.stopped;
     }
@@ -0,0 +1,98 @@
+use crate::music::{Part, PartNumber, PianoPhase};
+
+// the synthesizer and most midi players bend up or down by 2 semitones unless told otherwise, which the midi export also sets
+pub const PITCH_BEND_RANGE_CENTS: f32 = 200.0;
+// the middle of the 14 bit pitch bend, where nothing is bent
+pub const PITCH_BEND_CENTER: i32 = 8192;
+
+// how the 12 pitches of the octave are tuned compared to each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Temperament {
+    Equal,
+    // 5-limit just intonation, where the major thirds and fifths above the tonic are pure
+    Just,
+    // a chain of pure fifths
+    Pythagorean,
+    // a chain of fifths that are a quarter of a syntonic comma narrower than pure, so that the major thirds are pure
+    QuarterCommaMeantone,
+}
+
+// which temperament the pianos are tuned in, and how out of tune they are with each other
+#[derive(Clone, Copy, Debug)]
+pub struct Tuning {
+    pub temperament: Temperament,
+    // the pitch class that the temperament is built on, where 0 is C
+    // the patterns are in e, so the temperaments sound best built on 4
+    pub tonic: u8,
+    // added to every note of the part, in cents, like two real pianos that were not tuned together
+    pub part1_detune: f32,
+    pub part2_detune: f32,
+}
+
+impl Tuning {
+    pub const EQUAL: Tuning = Tuning { temperament: Temperament::Equal, tonic: 4, part1_detune: 0.0, part2_detune: 0.0 };
+    pub const JUST: Tuning = Tuning { temperament: Temperament::Just, tonic: 4, part1_detune: 0.0, part2_detune: 0.0 };
+    // equal temperament, with part 2's piano a little sharp, which makes the unisons beat
+    pub const TWO_PIANOS: Tuning = Tuning { temperament: Temperament::Equal, tonic: 4, part1_detune: 0.0, part2_detune: 4.0 };
+
+    // how far the pitch is played from equal temperament, in cents
+    pub fn cents(&self, pitch: u8, part_number: PartNumber) -> f32 {
+        let degree = (pitch % 12 + 12 - self.tonic % 12) % 12;
+        let detune = match part_number {
+            PartNumber::Part1 => self.part1_detune,
+            PartNumber::Part2 => self.part2_detune,
+        };
+        self.temperament.cents(degree) + detune
+    }
+
+    // tunes every note of the music
+    // like humanizing, this has to be done again when notes are added (like when phasing live)
+    pub fn apply(&self, music: &mut PianoPhase) {
+        self.apply_to_part(&mut music.part1, PartNumber::Part1);
+        self.apply_to_part(&mut music.part2, PartNumber::Part2);
+    }
+
+    fn apply_to_part(&self, part: &mut Part, part_number: PartNumber) {
+        for note in &mut part.flattened {
+            note.performance.cents = self.cents(note.pitch, part_number);
+        }
+    }
+}
+
+impl Temperament {
+    // how far the degree of the scale (in semitones above the tonic) is from equal temperament, in cents
+    pub fn cents(self, degree: u8) -> f32 {
+        let degree = (degree % 12) as usize;
+        let cents = match self {
+            Temperament::Equal => return 0.0,
+            Temperament::Just => ratio_to_cents(JUST_RATIOS[degree]),
+            Temperament::Pythagorean => ratio_to_cents(PYTHAGOREAN_RATIOS[degree]),
+            Temperament::QuarterCommaMeantone => {
+                // the degree is reached by going between 5 fifths down and 6 fifths up from the tonic, which puts the wolf fifth between the sharpest and the flattest degree
+                let fifths = (-5..=6).find(|fifths: &i32| (fifths * 7).rem_euclid(12) as usize == degree).unwrap();
+                let meantone_fifth = 1200.0 * 5f32.log2() / 4.0;
+                (fifths as f32 * meantone_fifth).rem_euclid(1200.0)
+            }
+        };
+        cents - 100.0 * degree as f32
+    }
+}
+
+const JUST_RATIOS: [(u32, u32); 12] = [(1, 1), (16, 15), (9, 8), (6, 5), (5, 4), (4, 3), (45, 32), (3, 2), (8, 5), (5, 3), (9, 5), (15, 8)];
+const PYTHAGOREAN_RATIOS: [(u32, u32); 12] =
+    [(1, 1), (256, 243), (9, 8), (32, 27), (81, 64), (4, 3), (729, 512), (3, 2), (128, 81), (27, 16), (16, 9), (243, 128)];
+
+fn ratio_to_cents((numerator, denominator): (u32, u32)) -> f32 {
+    1200.0 * (numerator as f32 / denominator as f32).log2()
+}
+
+// the 14 bit pitch bend that plays a note the given number of cents away from its pitch
+// a bend retunes every note that is sounding on the channel, and a humanized note can still be held when the next one starts,
+// so the midi export and the player release the note before when the bend changes, which cuts its legato short
+pub fn pitch_bend(cents: f32) -> u16 {
+    let bend = PITCH_BEND_CENTER + (cents / PITCH_BEND_RANGE_CENTS * PITCH_BEND_CENTER as f32).round() as i32;
+    bend.clamp(0, 2 * PITCH_BEND_CENTER - 1) as u16
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use super::*;
+
+// close enough for anyone to hear, which leaves room for floating point error
+const TOLERANCE_CENTS: f32 = 0.01;
+
+fn assert_cents(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < TOLERANCE_CENTS, "{actual} cents instead of {expected}");
+}
+
+#[test]
+fn equal_temperament_changes_nothing() {
+    for pitch in 0..128 {
+        assert_eq!(Tuning::EQUAL.cents(pitch, PartNumber::Part1), 0.0);
+        assert_eq!(Tuning::EQUAL.cents(pitch, PartNumber::Part2), 0.0);
+    }
+}
+
+#[test]
+fn intervals_above_the_tonic_are_pure() {
+    let pure_fifth = 1200.0 * 1.5f32.log2();
+    let pure_major_third = 1200.0 * 1.25f32.log2();
+    // built on e, so b is the fifth and g# is the major third
+    let just = Tuning::JUST;
+    assert_cents(just.cents(64, PartNumber::Part1), 0.0);
+    assert_cents(just.cents(71, PartNumber::Part1) + 700.0, pure_fifth);
+    assert_cents(just.cents(68, PartNumber::Part1) + 400.0, pure_major_third);
+    // the same in every octave
+    assert_cents(just.cents(59, PartNumber::Part1), just.cents(71, PartNumber::Part1));
+
+    assert_cents(Temperament::Pythagorean.cents(7) + 700.0, pure_fifth);
+    assert_cents(Temperament::QuarterCommaMeantone.cents(4) + 400.0, pure_major_third);
+}
+
+#[test]
+fn every_fifth_of_meantone_is_the_same_but_the_wolf() {
+    let fifth = |degree: u8| Temperament::QuarterCommaMeantone.cents((degree + 7) % 12) + 700.0 - Temperament::QuarterCommaMeantone.cents(degree);
+    let meantone_fifth = 1200.0 * 5f32.log2() / 4.0;
+    let wolves: Vec<_> = (0..12).filter(|&degree| (fifth(degree) - meantone_fifth).abs() > TOLERANCE_CENTS).collect();
+    // the fifth from the end of the chain of fifths (6 fifths above the tonic) back around to its start
+    assert_eq!(wolves, [6]);
+}
+
+#[test]
+fn detuning_only_changes_its_part() {
+    let tuning = Tuning { part2_detune: 4.0, ..Tuning::JUST };
+    for pitch in 64..=77 {
+        assert_cents(tuning.cents(pitch, PartNumber::Part2) - tuning.cents(pitch, PartNumber::Part1), 4.0);
+        assert_cents(tuning.cents(pitch, PartNumber::Part1), Tuning::JUST.cents(pitch, PartNumber::Part1));
+    }
+}
+
+#[test]
+fn pitch_bend_covers_the_range() {
+    assert_eq!(pitch_bend(0.0), 8192);
+    assert_eq!(pitch_bend(-PITCH_BEND_RANGE_CENTS), 0);
+    assert_eq!(pitch_bend(PITCH_BEND_RANGE_CENTS), 16383);
+    assert_eq!(pitch_bend(PITCH_BEND_RANGE_CENTS / 2.0), 8192 + 4096);
+}
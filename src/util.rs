@@ -1,4 +1,3 @@
-use macroquad::math::Vec2;
 use num_traits::Float;
 
 pub fn lerp<T: Float>(a: T, b: T, t: T) -> T {
@@ -10,6 +9,43 @@ pub fn remap<T: Float>(v: T, a1: T, b1: T, a2: T, b2: T) -> T {
     lerp(a2, b2, normalized)
 }
 
-pub fn circle_coord(center_x: f32, center_y: f32, radius: f32, angle: f32) -> Vec2 {
-    Vec2::new(center_x + angle.cos() * radius, center_y + angle.sin() * radius)
+// splitmix64, which is enough randomness for humanizing and for the random scores in the tests, and does not need any dependencies
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    // the sequence is decided by all of the keys together
+    pub(crate) fn new(keys: &[u64]) -> Random {
+        let mut random = Random { state: 0x853c_49e6_748f_ea9b };
+        for key in keys {
+            random.state ^= key;
+            random.state = random.next_u64();
+        }
+        random
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // between 0 (exclusive) and 1 (inclusive)
+    pub(crate) fn uniform(&mut self) -> f32 {
+        ((self.next_u64() >> 40) + 1) as f32 / (1u64 << 24) as f32
+    }
+
+    // standard normal distribution, using the box-muller transform
+    pub(crate) fn normal(&mut self) -> f32 {
+        let (u1, u2) = (self.uniform(), self.uniform());
+        (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn circle_coord(center_x: f32, center_y: f32, radius: f32, angle: f32) -> macroquad::math::Vec2 {
+    macroquad::math::Vec2::new(center_x + angle.cos() * radius, center_y + angle.sin() * radius)
 }
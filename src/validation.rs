@@ -0,0 +1,129 @@
+use itertools::Itertools;
+use num_rational::{Ratio, Rational32};
+use num_traits::ToPrimitive;
+
+use crate::{
+    error::{Diagnostic, Location, ScoreError, ScoreProblem},
+    export::{ticks_per_note, MAX_TICKS_PER_NOTE},
+    music::{Part, PartNumber, PianoPhase, DRAWABLE_PITCHES},
+};
+
+impl PianoPhase {
+    // checks everything that the rest of the program assumes about the score, so that a broken score is rejected before it is played
+    // every problem is reported instead of only the first one, so that a score can be fixed all at once
+    pub fn validate(&self) -> Result<(), ScoreError> {
+        let mut diagnostics = Vec::new();
+        for part_number in [PartNumber::Part1, PartNumber::Part2] {
+            self.validate_part(part_number, &mut diagnostics);
+        }
+        self.validate_phasing(&mut diagnostics);
+
+        let (part1_end, part2_end) = (self.part1.end_time(), self.part2.end_time());
+        if part1_end != part2_end {
+            diagnostics.push(Diagnostic {
+                location: Some(self.location(std::cmp::min(part1_end, part2_end), None)),
+                problem: ScoreProblem::PartsEndAtDifferentTimes { part1: part1_end, part2: part2_end },
+            });
+        }
+
+        // each segment can fit into a midi file on its own while all of them together do not
+        let ticks = ticks_per_note(self.part1.flattened.iter().chain(&self.part2.flattened));
+        if ticks > MAX_TICKS_PER_NOTE && diagnostics.iter().all(|diagnostic| !matches!(diagnostic.problem, ScoreProblem::TooManyTicksPerNote { .. }))
+        {
+            diagnostics.push(Diagnostic { location: None, problem: ScoreProblem::TooManyTicksPerNote { ticks } });
+        }
+
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(ScoreError { diagnostics })
+        }
+    }
+
+    fn validate_part(&self, part_number: PartNumber, diagnostics: &mut Vec<Diagnostic>) {
+        let part = self.part(part_number);
+        for (segment_index, segment) in part.segments.iter().enumerate() {
+            let location = || self.location(segment.start_time, Some((part_number, segment_index)));
+
+            let previous_end = if segment_index == 0 { Ratio::ZERO } else { part.segments[segment_index - 1].end_time };
+            if segment.start_time != previous_end || segment.end_time < segment.start_time {
+                diagnostics.push(Diagnostic { location: Some(location()), problem: ScoreProblem::SegmentsOutOfOrder });
+            }
+            if segment.pattern.0.is_empty() {
+                diagnostics.push(Diagnostic { location: Some(location()), problem: ScoreProblem::EmptyPattern });
+            }
+
+            for pitch in segment.pattern.0.iter().map(|note| note.pitch).filter(|pitch| !DRAWABLE_PITCHES.contains(pitch)).unique() {
+                diagnostics.push(Diagnostic { location: Some(location()), problem: ScoreProblem::UndrawablePitch { pitch } });
+            }
+        }
+
+        for (segment_index, notes) in &part.flattened.iter().chunk_by(|note| note.segment_index) {
+            let ticks = ticks_per_note(notes);
+            if ticks > MAX_TICKS_PER_NOTE {
+                diagnostics.push(Diagnostic {
+                    location: Some(self.location(part.segments[segment_index].start_time, Some((part_number, segment_index)))),
+                    problem: ScoreProblem::TooManyTicksPerNote { ticks },
+                });
+            }
+        }
+    }
+
+    // part 2 phases ahead of part 1 one note at a time, and once it is a whole pattern ahead it catches up by playing one more measure,
+    // so that the segments of both parts line up again
+    fn validate_phasing(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let mut notes_ahead = Ratio::ZERO;
+        for (segment_index, segment) in self.part2.segments.iter().enumerate() {
+            let location = || self.location(segment.start_time, Some((PartNumber::Part2, segment_index)));
+            let Some(part1_segment) = self.part1.find_segment_for_time(segment.start_time.to_f32().unwrap()).map(|i| &self.part1.segments[i]) else {
+                continue;
+            };
+            // already reported by validate_part, and there is nothing to phase or catch up with
+            if segment.pattern.0.is_empty() {
+                continue;
+            }
+            let pattern_len = segment.pattern.0.len() as i32;
+
+            if segment.speed != Ratio::ONE {
+                let part1_pattern_len = part1_segment.pattern.0.len();
+                if part1_pattern_len != segment.pattern.0.len() {
+                    diagnostics.push(Diagnostic {
+                        location: Some(location()),
+                        problem: ScoreProblem::PatternLengthsDiffer { part1: part1_pattern_len, part2: segment.pattern.0.len() },
+                    });
+                }
+
+                // how many more notes part 2 plays than part 1 does in the same time
+                let notes_played = Ratio::from_integer(pattern_len * segment.repetitions as i32);
+                let advance = notes_played - (segment.end_time - segment.start_time) * part1_segment.speed;
+                if advance != Ratio::ONE {
+                    diagnostics.push(Diagnostic { location: Some(location()), problem: ScoreProblem::PhaseDoesNotAdvanceOneNote { advance } });
+                }
+                notes_ahead += advance;
+            } else if notes_ahead != Ratio::ZERO && (notes_ahead / pattern_len).is_integer() {
+                if !is_segment_boundary(&self.part1, segment.end_time) {
+                    diagnostics.push(Diagnostic { location: Some(location()), problem: ScoreProblem::CatchUpDoesNotLineUp });
+                }
+                notes_ahead = Ratio::ZERO;
+            }
+        }
+    }
+
+    fn location(&self, time: Rational32, segment: Option<(PartNumber, usize)>) -> Location {
+        let time_f32 = time.to_f32().unwrap();
+        Location {
+            time,
+            section: self.section_at(time_f32).map(|section| section.name),
+            rehearsal_mark: self.rehearsal_mark_at(time_f32).map(|rehearsal_mark| rehearsal_mark.number),
+            part_number: segment.map(|(part_number, _)| part_number),
+            segment_index: segment.map(|(_, segment_index)| segment_index),
+        }
+    }
+}
+
+fn is_segment_boundary(part: &Part, time: Rational32) -> bool {
+    part.segments.iter().any(|segment| segment.start_time == time || segment.end_time == time)
+}
+
+#[cfg(test)]
+mod tests;
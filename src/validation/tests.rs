@@ -0,0 +1,88 @@
+use num_rational::Ratio;
+
+use super::*;
+
+// the name of each problem, so that the broken scores can say which problem they have
+// there is no wildcard, so that a new problem cannot be added without a broken score for it below
+fn problem_name(problem: &ScoreProblem) -> &'static str {
+    match problem {
+        ScoreProblem::SegmentsOutOfOrder => "SegmentsOutOfOrder",
+        ScoreProblem::EmptyPattern => "EmptyPattern",
+        ScoreProblem::PartsEndAtDifferentTimes { .. } => "PartsEndAtDifferentTimes",
+        ScoreProblem::PatternLengthsDiffer { .. } => "PatternLengthsDiffer",
+        ScoreProblem::PhaseDoesNotAdvanceOneNote { .. } => "PhaseDoesNotAdvanceOneNote",
+        ScoreProblem::CatchUpDoesNotLineUp => "CatchUpDoesNotLineUp",
+        ScoreProblem::UndrawablePitch { .. } => "UndrawablePitch",
+        ScoreProblem::TooManyTicksPerNote { .. } => "TooManyTicksPerNote",
+    }
+}
+
+fn phasing_segments(part: &Part) -> Vec<usize> {
+    part.segments.iter().positions(|segment| segment.speed != Ratio::ONE).collect()
+}
+
+// the shortened piece, broken in one way for each problem
+fn broken_scores() -> Vec<(&'static str, PianoPhase)> {
+    let broken = |problem, break_score: &dyn Fn(&mut PianoPhase)| {
+        let mut music = PianoPhase::new_shortened(432);
+        break_score(&mut music);
+        (problem, music)
+    };
+    vec![
+        broken("SegmentsOutOfOrder", &|music| music.part1.segments[1].start_time += 1),
+        broken("EmptyPattern", &|music| music.part1.segments[0].pattern.0.clear()),
+        // after the first phase part 2 is a note ahead, which is compared to the length of the pattern after it
+        broken("EmptyPattern", &|music| {
+            let after_first_phase = phasing_segments(&music.part2)[0] + 1;
+            music.part2.segments[after_first_phase].pattern.0.clear();
+        }),
+        broken("PartsEndAtDifferentTimes", &|music| music.part2.segments.last_mut().unwrap().end_time += 1),
+        broken("PatternLengthsDiffer", &|music| {
+            let first_phase = phasing_segments(&music.part2)[0];
+            music.part2.segments[first_phase].pattern.0.pop();
+        }),
+        broken("PhaseDoesNotAdvanceOneNote", &|music| {
+            let first_phase = phasing_segments(&music.part2)[0];
+            music.part2.segments[first_phase].repetitions += 1;
+        }),
+        // part 2 is a whole pattern ahead after as many phases as the first pattern has notes, and then catches up a note late
+        broken("CatchUpDoesNotLineUp", &|music| {
+            let catch_up = phasing_segments(&music.part2)[music.part2.segments[0].pattern.0.len() - 1] + 1;
+            music.part2.segments[catch_up].end_time += 1;
+            for segment in &mut music.part2.segments[catch_up + 1..] {
+                segment.start_time += 1;
+                segment.end_time += 1;
+            }
+        }),
+        broken("UndrawablePitch", &|music| music.part1.segments[0].pattern.0[0].pitch = 60),
+        // 32749 is a prime, so every note would need that many ticks
+        broken("TooManyTicksPerNote", &|music| music.part1.flattened[0].length = Ratio::new(1, 32749)),
+    ]
+}
+
+#[test]
+fn every_problem_is_reported() {
+    for (problem, music) in broken_scores() {
+        let Err(error) = music.validate() else { panic!("{problem} is not reported") };
+        let reported: Vec<_> = error.diagnostics.iter().map(|diagnostic| problem_name(&diagnostic.problem)).collect();
+        assert!(reported.contains(&problem), "{problem} is not reported, only {reported:?}");
+    }
+}
+
+#[test]
+fn every_problem_has_a_broken_score() {
+    let broken: Vec<_> = broken_scores().into_iter().map(|(problem, _)| problem).collect();
+    let problems = [
+        ScoreProblem::SegmentsOutOfOrder,
+        ScoreProblem::EmptyPattern,
+        ScoreProblem::PartsEndAtDifferentTimes { part1: Ratio::ZERO, part2: Ratio::ZERO },
+        ScoreProblem::PatternLengthsDiffer { part1: 0, part2: 0 },
+        ScoreProblem::PhaseDoesNotAdvanceOneNote { advance: Ratio::ZERO },
+        ScoreProblem::CatchUpDoesNotLineUp,
+        ScoreProblem::UndrawablePitch { pitch: 0 },
+        ScoreProblem::TooManyTicksPerNote { ticks: 0 },
+    ];
+    for problem in &problems {
+        assert!(broken.contains(&problem_name(problem)), "there is no broken score with {}", problem_name(problem));
+    }
+}
@@ -1,103 +1,141 @@
 use macroquad::{
+    camera::{set_camera, set_default_camera, Camera2D},
+    color::Color,
     math::{clamp, Rect},
-    shapes::{draw_arc, draw_circle, draw_line, draw_rectangle},
-    text::{draw_text_ex, Font, TextParams},
-    window::{clear_background, screen_height, screen_width},
+    window::{screen_dpi_scale, screen_height, screen_width},
 };
 use num_rational::{Ratio, Rational32};
 use num_traits::{FloatConst, ToPrimitive};
 
 use crate::{
-    music::{Part, PianoPhase, Segment},
+    error::AssetError,
+    music::{Part, PartNumber, Pattern, PianoPhase, Segment},
+    practice::{Judgement, PracticeSession},
+    resultant::Resultant,
     timing::Timing,
     util::{lerp, remap},
     visualizer::{
-        colors::ChangeAlpha,
+        canvas::{Canvas, Screen, TextParams, Typeface},
+        colors::{ChangeAlpha, PartColors, Theme},
+        layout::{Layout, Panel},
         notation::{Staff, StaffPosition, CLEF_OFFSET, CLEF_WIDTH, DYNAMICS_Y, REPEAT_WIDTH, STEM_ABOVE_Y, STEM_BELOW_Y},
     },
 };
 
-mod colors;
-mod notation;
+pub mod canvas;
+pub mod colors;
+mod keyboard;
+pub mod layout;
+pub mod notation;
+mod phase_chart;
+mod piano_roll;
+pub mod raster;
+pub mod svg;
 
 pub struct Visualizer {
     notation_font: notation::Font,
-    text_font: Font,
+    text_font: Typeface,
+    layout: Layout,
+    theme: Theme,
 }
 
 impl Visualizer {
-    pub async fn new() -> Result<Visualizer, Box<dyn std::error::Error>> {
-        let text_font = macroquad::text::load_ttf_font("data/Besley/static/Besley-Regular.ttf").await?;
-        Ok(Visualizer { notation_font: notation::Font::load_bravura().await?, text_font })
+    // the theme is either the name of a built in theme or the path to a theme file
+    pub fn new(layout: Layout, theme: &str) -> Result<Visualizer, AssetError> {
+        let text_font = Typeface::load("data/Besley/static/Besley-Regular.ttf")?;
+        Ok(Visualizer { notation_font: notation::Font::load_bravura()?, text_font, layout, theme: Theme::load(theme)? })
     }
 
-    pub fn update(&mut self, timing: &Timing, music: &PianoPhase) {
-        clear_background(colors::BACKGROUND_COLOR);
+    // draws onto the window in physical pixels, so that the drawing is sharp on high dpi screens
+    pub fn update(&self, timing: &Timing, music: &PianoPhase, practice: Option<&PracticeSession>) {
+        let width = screen_width() * screen_dpi_scale();
+        let height = screen_height() * screen_dpi_scale();
+        // the height is negative so that y points down like it does without a camera
+        set_camera(&Camera2D::from_display_rect(Rect::new(0.0, height, width, -height)));
+        self.draw(&mut Screen, timing.current_musical_time(music), music, practice, width, height);
+        set_default_camera();
+    }
 
-        let current_time = timing.current_musical_time(music);
+    // draws the moment of the music at the current time, filling the rectangle from (0, 0) to (width, height)
+    pub fn draw(&self, canvas: &mut dyn Canvas, current_time: f32, music: &PianoPhase, practice: Option<&PracticeSession>, width: f32, height: f32) {
+        let theme = &self.theme;
+        canvas.clear(theme.background);
 
         let part1_segment_index = music.part1.find_segment_for_time(current_time);
         let part2_segment_index = music.part2.find_segment_for_time(current_time);
 
-        let screen_width = screen_width();
-        let screen_height = screen_height();
-
-        draw_status_text(
-            &self.text_font,
-            &self.notation_font,
-            music,
-            current_time,
-            (screen_width / 50.0).ceil() as u16,
-            part1_segment_index,
-            part2_segment_index,
-        );
-
-        let wheel_radius = f32::min(screen_width * 0.5 * 0.4 * 0.8, screen_height * 0.5 * 0.4 * 0.8);
-        if let Some(part1_segment_index) = part1_segment_index {
-            draw_wheel(
-                &self.notation_font,
-                current_time,
-                &music.part1.segments[part1_segment_index],
-                screen_width * 0.25,
-                screen_height * 0.3,
-                wheel_radius,
-            );
-        }
-        if let Some(part2_segment_index) = part2_segment_index {
-            draw_wheel(
-                &self.notation_font,
-                current_time,
-                &music.part2.segments[part2_segment_index],
-                screen_width * 0.75,
-                screen_height * 0.3,
-                wheel_radius,
-            );
+        for placement in self.layout.panels {
+            let window = placement.area.to_rect(width, height);
+            match placement.panel {
+                Panel::StatusText => draw_status_text(
+                    canvas,
+                    &self.text_font,
+                    &self.notation_font,
+                    theme,
+                    music,
+                    window,
+                    current_time,
+                    part1_segment_index,
+                    part2_segment_index,
+                    practice,
+                ),
+                Panel::PhaseDiagram => draw_phase_diagram(canvas, &self.text_font, theme, music, window, current_time),
+                Panel::Wheel(part_number) => {
+                    let segment_index = match part_number {
+                        PartNumber::Part1 => part1_segment_index,
+                        PartNumber::Part2 => part2_segment_index,
+                    };
+                    if let Some(segment_index) = segment_index {
+                        draw_wheel(
+                            canvas,
+                            &self.notation_font,
+                            theme,
+                            theme.part(part_number),
+                            current_time,
+                            &music.part(part_number).segments[segment_index],
+                            window.center().x,
+                            window.center().y,
+                            f32::min(window.w, window.h) * 0.4,
+                        );
+                    }
+                }
+                Panel::Keyboard => keyboard::draw_keyboard(canvas, &self.text_font, theme, music, window, current_time),
+                Panel::InSyncStaff => draw_in_sync_staff(canvas, &self.notation_font, theme, music, window, current_time, practice),
+                Panel::OutOfSyncStaves => {
+                    draw_out_of_sync_staff(canvas, &self.notation_font, theme, music, window, current_time, part1_segment_index, part2_segment_index)
+                }
+                Panel::ResultantStaff => draw_resultant_staff(canvas, &self.notation_font, theme, music, window, current_time),
+                Panel::PianoRoll => piano_roll::draw_piano_roll(canvas, theme, music, window, current_time),
+            }
         }
+    }
 
-        draw_in_sync_staff(&self.notation_font, music, Rect::new(0.0, screen_height * 0.5, screen_width, screen_height * 0.5 * 0.333), current_time);
-        draw_out_of_sync_staff(
-            &self.notation_font,
-            music,
-            Rect::new(0.0, screen_height * (0.5 + 0.5 * 0.333), screen_width, screen_height * 0.5 * 0.667),
-            current_time,
-            part1_segment_index,
-            part2_segment_index,
-        );
+    // draws a map of the whole piece for printing instead of a moment of it, filling the rectangle from (0, 0) to (width, height)
+    pub fn draw_phase_chart(&self, canvas: &mut dyn Canvas, music: &PianoPhase, width: f32, height: f32) {
+        canvas.clear(self.theme.background);
+        phase_chart::draw_phase_chart(canvas, &self.notation_font, &self.text_font, &self.theme, music, Rect::new(0.0, 0.0, width, height));
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_status_text(
-    text_font: &Font,
+    canvas: &mut dyn Canvas,
+    text_font: &Typeface,
     notation_font: &notation::Font,
+    theme: &Theme,
     music: &PianoPhase,
+    window: Rect,
     current_time: f32,
-    font_size: u16,
     part1_segment_index: Option<usize>,
     part2_segment_index: Option<usize>,
+    practice: Option<&PracticeSession>,
 ) {
-    let left_x: f32 = font_size as f32 * 0.75;
+    // room for five lines of text
+    let font_size = (window.h / 7.5).ceil() as u16;
+    let left_x = window.x + font_size as f32 * 0.75;
+    let line_y = |line: f32| window.y + font_size as f32 * 1.5 * line;
 
-    let go = |segment: &Segment, part_name: &'static str, y_position: f32| {
+    let go = |canvas: &mut dyn Canvas, segment: &Segment, part_name: &'static str, y_position: f32| {
         let status = if segment.speed != Ratio::ONE { "Phasing" } else { "Steady" };
 
         let bpm = music.tempo as f32 / 2.0 * segment.speed.to_f32().unwrap();
@@ -105,43 +143,145 @@ fn draw_status_text(
         let current_measure = segment.find_measure(current_time).number + 1;
         let measures_in_segment = segment.repetitions;
 
-        let first_part_dims = draw_text_ex(
-            &format!("{part_name}: {status} "),
-            left_x,
-            y_position,
-            TextParams { font: Some(text_font), font_size, color: colors::FOREGROUND_COLOR, ..Default::default() },
-        );
-        let eigth_note_dims = draw_text_ex(
+        let first_part_dims =
+            canvas.draw_text(&format!("{part_name}: {status} "), left_x, y_position, &TextParams::new(text_font, font_size, theme.foreground));
+        let eigth_note_dims = canvas.draw_text(
             &smufl::Glyph::MetNote8thUp.codepoint().to_string(),
             left_x + first_part_dims.width,
             y_position,
-            notation_font.make_text_params_with_size(font_size, colors::FOREGROUND_COLOR),
+            &notation_font.make_text_params_with_size(font_size, theme.foreground),
         );
-        draw_text_ex(
+        canvas.draw_text(
             &format!(" = {bpm:.1} ({current_measure}/{measures_in_segment})"),
             left_x + first_part_dims.width + eigth_note_dims.width,
             y_position,
-            TextParams { font: Some(text_font), font_size, color: colors::FOREGROUND_COLOR, ..Default::default() },
+            &TextParams::new(text_font, font_size, theme.foreground),
         );
     };
 
+    let mut landmark_x = left_x;
+    if let Some(section) = music.section_at(current_time) {
+        landmark_x +=
+            canvas.draw_text(&format!("{} ", section.name), left_x, line_y(1.0), &TextParams::new(text_font, font_size, theme.foreground)).width;
+    }
+    if let Some(rehearsal_mark) = music.rehearsal_mark_at(current_time) {
+        draw_rehearsal_mark(canvas, text_font, theme, rehearsal_mark.number, landmark_x, line_y(1.0), font_size);
+    }
+
     if let Some(part1_segment_index) = part1_segment_index {
-        go(&music.part1.segments[part1_segment_index], "Piano 1", font_size as f32 * 1.5);
+        go(canvas, &music.part1.segments[part1_segment_index], "Piano 1", line_y(2.0));
     }
     if let Some(part2_segment_index) = part2_segment_index {
-        go(&music.part2.segments[part2_segment_index], "Piano 2", font_size as f32 * 1.5 * 2.0);
+        go(canvas, &music.part2.segments[part2_segment_index], "Piano 2", line_y(3.0));
+    }
+
+    if let Some(practice) = practice {
+        let score = practice.score();
+        canvas.draw_text(
+            &format!("Practice: {:.1}%", score.percentage()),
+            left_x,
+            line_y(4.0),
+            &TextParams::new(text_font, font_size, theme.foreground),
+        );
+        canvas.draw_text(&score.to_string(), left_x, line_y(5.0), &TextParams::new(text_font, font_size, theme.foreground));
+    }
+}
+
+// a rehearsal mark number in a box, like in the score, with the baseline of the number at y
+fn draw_rehearsal_mark(canvas: &mut dyn Canvas, text_font: &Typeface, theme: &Theme, number: u32, x: f32, y: f32, font_size: u16) {
+    let padding = font_size as f32 * 0.2;
+    let text = number.to_string();
+    let params = TextParams::new(text_font, font_size, theme.foreground);
+    let dimensions = canvas.measure_text(&text, &params);
+    canvas.draw_text(&text, x + padding, y, &params);
+    canvas.draw_rectangle_lines(
+        x,
+        y - dimensions.offset_y - padding,
+        dimensions.width + padding * 2.0,
+        dimensions.height + padding * 2.0,
+        font_size as f32 * 0.08,
+        theme.foreground,
+    );
+}
+
+// plots the phase offset between the parts over the whole piece, with a cursor at the current time
+// the rehearsal marks are shown along the top, and a longer line marks the start of each section
+fn draw_phase_diagram(canvas: &mut dyn Canvas, text_font: &Typeface, theme: &Theme, music: &PianoPhase, window: Rect, current_time: f32) {
+    const SAMPLES: usize = 1024;
+
+    let end_time = music.end_time().to_f32().unwrap();
+    let max_offset = music.part1.segments.iter().map(|segment| segment.pattern.0.len()).max().unwrap_or(1) as f32;
+    let line_thickness = window.h / 80.0;
+
+    let time_to_x = |time: f32| remap(time, 0.0, end_time, window.x, window.x + window.w);
+    let offset_to_y = |offset: f32| remap(offset, 0.0, max_offset, window.y + window.h, window.y);
+
+    // one grid line for every note of offset
+    for offset in 0..=(max_offset as usize) {
+        let y = offset_to_y(offset as f32);
+        canvas.draw_line(window.x, y, window.x + window.w, y, line_thickness * 0.5, theme.foreground.modify_a(|a| a * 0.15));
+    }
+
+    let label_font_size = (window.h / 8.0).ceil() as u16;
+    for section in &music.sections {
+        let x = time_to_x(section.start_time.to_f32().unwrap());
+        canvas.draw_line(x, window.y, x, window.y + window.h, line_thickness * 0.5, theme.foreground.modify_a(|a| a * 0.5));
+    }
+    for rehearsal_mark in &music.rehearsal_marks {
+        let x = time_to_x(rehearsal_mark.time.to_f32().unwrap());
+        canvas.draw_line(x, window.y, x, window.y + window.h * 0.1, line_thickness * 0.5, theme.foreground.modify_a(|a| a * 0.5));
+        canvas.draw_text(
+            &rehearsal_mark.number.to_string(),
+            x + line_thickness,
+            window.y + label_font_size as f32,
+            &TextParams::new(text_font, label_font_size, theme.foreground.modify_a(|a| a * 0.5)),
+        );
+    }
+
+    let mut last_point: Option<(f32, f32)> = None;
+    for sample in 0..=SAMPLES {
+        let time = sample as f32 / SAMPLES as f32 * end_time;
+        let Some(offset) = music.phase_offset(time) else {
+            last_point = None;
+            continue;
+        };
+
+        if let Some((last_time, last_offset)) = last_point {
+            // when the offset wraps around to 0 at the end of a section, there should be a gap instead of a line going all the way down
+            if offset >= last_offset - max_offset * 0.5 {
+                let color = if time <= current_time { theme.important_foreground } else { theme.foreground.modify_a(|a| a * 0.5) };
+                canvas.draw_line(time_to_x(last_time), offset_to_y(last_offset), time_to_x(time), offset_to_y(offset), line_thickness, color);
+            }
+        }
+        last_point = Some((time, offset));
+    }
+
+    let cursor_x = time_to_x(current_time.clamp(0.0, end_time));
+    canvas.draw_line(cursor_x, window.y, cursor_x, window.y + window.h, line_thickness * 0.5, theme.foreground);
+    if let Some(current_offset) = music.phase_offset(current_time) {
+        canvas.draw_circle(cursor_x, offset_to_y(current_offset), line_thickness * 1.5, theme.important_foreground);
     }
 }
 
-fn draw_wheel(font: &notation::Font, current_time: f32, segment: &Segment, center_x: f32, center_y: f32, staff_outer_radius: f32) {
-    let staff =
-        Staff::new(font, StaffPosition::Circular { center_x, center_y, outer_radius: staff_outer_radius }, (staff_outer_radius * 0.15 / 4.0) as u16);
-    staff.draw(colors::FOREGROUND_COLOR);
+#[allow(clippy::too_many_arguments)]
+fn draw_wheel(
+    canvas: &mut dyn Canvas,
+    font: &notation::Font,
+    theme: &Theme,
+    part_colors: &PartColors,
+    current_time: f32,
+    segment: &Segment,
+    center_x: f32,
+    center_y: f32,
+    staff_outer_radius: f32,
+) {
+    let staff = Staff::new(font, StaffPosition::Circular { center_x, center_y, outer_radius: staff_outer_radius }, staff_outer_radius * 0.15 / 4.0);
+    staff.draw(canvas, theme.foreground);
 
-    let dot_radius = staff_outer_radius - STEM_BELOW_Y * staff.staff_space as f32 - 20.0;
-    let spinner_radius = staff_outer_radius - STEM_BELOW_Y * staff.staff_space as f32 - 40.0;
+    let dot_radius = staff_outer_radius - (STEM_BELOW_Y + 0.8) * staff.staff_space;
+    let spinner_radius = staff_outer_radius - (STEM_BELOW_Y + 1.6) * staff.staff_space;
 
-    let spinner_thickness = 0.5 * staff.staff_space as f32;
+    let spinner_thickness = 0.5 * staff.staff_space;
 
     let offset_in_segment =
         (current_time - segment.start_time.to_f32().unwrap()) / (segment.end_time.to_f32().unwrap() - segment.start_time.to_f32().unwrap());
@@ -150,41 +290,52 @@ fn draw_wheel(font: &notation::Font, current_time: f32, segment: &Segment, cente
     let current_note_index = (offset_in_measure * segment.pattern.0.len() as f32).floor() as usize;
     let offset_in_measure_rounded = current_note_index as f32 / segment.pattern.0.len() as f32;
 
-    let current_dynamic = segment.dynamic.interpolate(offset_in_segment);
+    let current_dynamic = segment.dynamic.interpolate_relative(offset_in_segment);
 
-    let thing_color = colors::FOREGROUND_COLOR.modify_a(|a| a * current_dynamic);
-    let normal_note_color = thing_color;
-    let highlighted_note_color = colors::IMPORTANT_FOREGROUND_COLOR.modify_a(|a| a * current_dynamic);
-    let highlight_color = colors::HIGHLIGHT_COLOR.modify_a(|a| a * current_dynamic);
+    let thing_color = theme.foreground.modify_a(|a| a * current_dynamic);
+    let normal_note_color = part_colors.notes.modify_a(|a| a * current_dynamic);
+    let highlighted_note_color = part_colors.current_note.modify_a(|a| a * current_dynamic);
+    let highlight_color = theme.highlight.modify_a(|a| a * current_dynamic);
 
     let spinner_end_x = center_x + (offset_in_measure * f32::TAU() - f32::PI() / 2.0).cos() * spinner_radius;
     let spinner_end_y = center_y + (offset_in_measure * f32::TAU() - f32::PI() / 2.0).sin() * spinner_radius;
-    draw_line(center_x, center_y, spinner_end_x, spinner_end_y, spinner_thickness, thing_color);
+    canvas.draw_line(center_x, center_y, spinner_end_x, spinner_end_y, spinner_thickness, thing_color);
 
     let dot_x = center_x + (offset_in_measure_rounded * f32::TAU() - f32::PI() / 2.0).cos() * dot_radius;
     let dot_y = center_y + (offset_in_measure_rounded * f32::TAU() - f32::PI() / 2.0).sin() * dot_radius;
-    draw_circle(dot_x, dot_y, spinner_thickness * 0.55, thing_color);
+    canvas.draw_circle(dot_x, dot_y, spinner_thickness * 0.55, thing_color);
 
-    draw_arc(
+    canvas.draw_arc(
         center_x,
         center_y,
-        56,
-        staff_outer_radius - staff.staff_height as f32,
+        staff_outer_radius - staff.staff_height,
         -90.0,
-        staff.staff_height as f32,
+        staff.staff_height,
         360.0 * offset_in_measure,
         highlight_color,
     );
 
-    for (note_i, note) in segment.pattern.0.iter().enumerate() {
-        let note_angle = remap(note_i as f32, 0.0, segment.pattern.0.len() as f32, 0.0, f32::TAU());
+    draw_wheel_notes(canvas, &staff, &segment.pattern, normal_note_color, highlighted_note_color, Some(current_note_index));
+}
+
+// the notes of the pattern around a circular staff, starting at the top and going clockwise
+fn draw_wheel_notes(
+    canvas: &mut dyn Canvas,
+    staff: &Staff,
+    pattern: &Pattern,
+    note_color: Color,
+    current_note_color: Color,
+    current_note_index: Option<usize>,
+) {
+    for (note_i, note) in pattern.0.iter().enumerate() {
+        let note_angle = remap(note_i as f32, 0.0, pattern.0.len() as f32, 0.0, f32::TAU());
 
         // only the first and last notes draw beams to simplify things
         // we can't just draw to a fixed offset because that would draw the beam to a certain angle which doesn't account for the stem offset
         let (beam_left, beam_right) = if note_i == 0 || note_i == 1 {
             // draw a beam from the first note to the bottom of the staff
             (None, Some(f32::PI() - 1.0))
-        } else if note_i == segment.pattern.0.len() - 1 || note_i == segment.pattern.0.len() - 2 {
+        } else if note_i == pattern.0.len() - 1 || note_i == pattern.0.len() - 2 {
             // draw a beam from the bottom of the staff to the last note
             (Some(f32::PI() - 1.0), None)
         } else {
@@ -196,94 +347,177 @@ fn draw_wheel(font: &notation::Font, current_time: f32, segment: &Segment, cente
             crate::music::Hand::Right => STEM_ABOVE_Y,
         };
 
-        let note_color = if note_i == current_note_index { highlighted_note_color } else { normal_note_color };
+        let color = if Some(note_i) == current_note_index { current_note_color } else { note_color };
 
-        staff.draw_note(note_angle, note.pitch, note_color, normal_note_color, stem_end_y, 2, beam_left, beam_right)
+        staff.draw_note(canvas, note_angle, note.pitch, color, note_color, stem_end_y, 2, beam_left, beam_right);
+        if note.accent {
+            staff.draw_accent(canvas, note_angle, stem_end_y, color);
+        }
     }
 }
 
-fn draw_in_sync_staff(font: &notation::Font, music: &PianoPhase, window: Rect, current_time: f32) {
+fn draw_in_sync_staff(
+    canvas: &mut dyn Canvas,
+    font: &notation::Font,
+    theme: &Theme,
+    music: &PianoPhase,
+    window: Rect,
+    current_time: f32,
+    practice: Option<&PracticeSession>,
+) {
     let base_time_segment_index = music.part1.find_segment_for_time(current_time);
     if let Some(base_time_segment_index) = base_time_segment_index {
         let window_length = music.part1.segments[base_time_segment_index].single_measure_duration();
 
-        let staff_space = (window.w / 120.0) as u16;
+        let staff_space = f32::min(window.w / 120.0, window.h / 12.0);
         let note_horiz_space = 8.0;
-        let staff_width = (window_length.to_f32().unwrap() * note_horiz_space + CLEF_OFFSET + CLEF_WIDTH) * staff_space as f32;
+        let staff_width = (window_length.to_f32().unwrap() * note_horiz_space + CLEF_OFFSET + CLEF_WIDTH) * staff_space;
         let staff_left = window.x + window.w * 0.5 - staff_width * 0.5;
-        let staff_top = window.y + window.h * 0.5 - staff_space as f32 * 2.0; // center the staff vertically
+        let staff_top = window.y + window.h * 0.5 - staff_space * 2.0; // center the staff vertically
 
         let staff = Staff::new(font, StaffPosition::Straight { top: staff_top, left: staff_left, right: staff_left + staff_width }, staff_space);
 
-        staff.draw(colors::FOREGROUND_COLOR);
+        staff.draw(canvas, theme.foreground);
 
-        let draw_past_notes = |staff: &Staff, part: &Part, window_duration: Rational32, stem_end_y: f32| {
-            let notes = part.find_note_range(
-                |note| note.time.to_f32().unwrap() < (current_time - window_duration.to_f32().unwrap()),
-                |note| note.time.to_f32().unwrap() <= current_time,
-            );
+        let draw_past_notes =
+            |canvas: &mut dyn Canvas, staff: &Staff, part: &Part, part_colors: &PartColors, window_duration: Rational32, stem_end_y: f32| {
+                let notes = part.find_note_range(
+                    |note| note.time.to_f32().unwrap() < (current_time - window_duration.to_f32().unwrap()),
+                    |note| note.time.to_f32().unwrap() <= current_time,
+                );
 
-            staff.draw_treble_clef(CLEF_OFFSET, colors::FOREGROUND_COLOR);
-            let notes_left = CLEF_OFFSET + CLEF_WIDTH;
-
-            for note in notes {
-                // TODO: clean up this code
-                let base_speed_segment = &music.part1.segments[music.part1.find_segment_for_time(note.time.to_f32().unwrap()).unwrap()];
-                let base_speed_measure = base_speed_segment.find_measure(note.time.to_f32().unwrap());
-
-                let remap_time_to_x = |time| {
-                    remap(
-                        time,
-                        base_speed_measure.start_time.to_f32().unwrap(),
-                        base_speed_measure.end_time.to_f32().unwrap(),
-                        notes_left,
-                        base_speed_segment.single_measure_duration().to_f32().unwrap() * note_horiz_space + notes_left,
-                    )
-                };
-
-                let note_x = remap_time_to_x(note.time.to_f32().unwrap());
-
-                let note_fade = clamp(
-                    remap(
-                        note.time.to_f32().unwrap(),
-                        current_time - window_duration.to_f32().unwrap() * 0.75,
-                        current_time - window_duration.to_f32().unwrap() * 0.25,
+                staff.draw_treble_clef(canvas, CLEF_OFFSET, theme.foreground);
+                let notes_left = CLEF_OFFSET + CLEF_WIDTH;
+
+                for note in notes {
+                    // TODO: clean up this code
+                    let base_speed_segment = &music.part1.segments[music.part1.find_segment_for_time(note.time.to_f32().unwrap()).unwrap()];
+                    let base_speed_measure = base_speed_segment.find_measure(note.time.to_f32().unwrap());
+
+                    let remap_time_to_x = |time| {
+                        remap(
+                            time,
+                            base_speed_measure.start_time.to_f32().unwrap(),
+                            base_speed_measure.end_time.to_f32().unwrap(),
+                            notes_left,
+                            base_speed_segment.single_measure_duration().to_f32().unwrap() * note_horiz_space + notes_left,
+                        )
+                    };
+
+                    let note_x = remap_time_to_x(note.time.to_f32().unwrap());
+
+                    let note_fade = clamp(
+                        remap(
+                            note.time.to_f32().unwrap(),
+                            current_time - window_duration.to_f32().unwrap() * 0.75,
+                            current_time - window_duration.to_f32().unwrap() * 0.25,
+                            0.3,
+                            1.0,
+                        ),
                         0.3,
                         1.0,
-                    ),
-                    0.3,
-                    1.0,
-                );
+                    );
+
+                    let actual_segment = &part.segments[note.segment_index];
+                    let actual_measure = actual_segment.get_measure(note.measure_number);
+                    let notes_in_actual_measure = part.find_note_range(|n| n.time < actual_measure.start_time, |n| n.time < actual_measure.end_time);
+
+                    let left_beam_time = if note.time != notes_in_actual_measure[0].time { Some(note.time - Ratio::new(1, 2)) } else { None };
+                    let right_beam_time = if note.time != notes_in_actual_measure[notes_in_actual_measure.len() - 1].time {
+                        Some(note.time + Ratio::new(1, 2))
+                    } else {
+                        None
+                    };
+
+                    let left_beam_x = left_beam_time.map(|t| remap_time_to_x(t.to_f32().unwrap()));
+                    let right_beam_x = right_beam_time.map(|t| remap_time_to_x(t.to_f32().unwrap()));
+
+                    let note_color = if note.time == notes.last().unwrap().time { part_colors.current_note } else { part_colors.notes }
+                        .set_a(note.relative_volume(part) * note_fade);
+                    let beam_color = part_colors.notes.set_a(note.relative_volume(part) * note_fade);
+
+                    staff.draw_note(canvas, note_x, note.pitch, note_color, beam_color, stem_end_y, 2, left_beam_x, right_beam_x);
+                    if note.accent {
+                        staff.draw_accent(canvas, note_x, stem_end_y, note_color);
+                    }
+                }
+            };
 
-                let actual_segment = &part.segments[note.segment_index];
-                let actual_measure = actual_segment.get_measure(note.measure_number);
-                let notes_in_actual_measure = part.find_note_range(|n| n.time < actual_measure.start_time, |n| n.time < actual_measure.end_time);
+        draw_past_notes(canvas, &staff, &music.part1, &theme.part1, window_length, STEM_ABOVE_Y);
+        draw_past_notes(canvas, &staff, &music.part2, &theme.part2, window_length, STEM_BELOW_Y);
 
-                let left_beam_time = if note.time != notes_in_actual_measure[0].time { Some(note.time - Ratio::new(1, 2)) } else { None };
-                let right_beam_time = if note.time != notes_in_actual_measure[notes_in_actual_measure.len() - 1].time {
-                    Some(note.time + Ratio::new(1, 2))
-                } else {
-                    None
-                };
+        if let Some(practice) = practice {
+            draw_practice_feedback(canvas, &staff, theme, music, practice, window_length, note_horiz_space, current_time);
+        }
+    }
+}
 
-                let left_beam_x = left_beam_time.map(|t| remap_time_to_x(t.to_f32().unwrap()));
-                let right_beam_x = right_beam_time.map(|t| remap_time_to_x(t.to_f32().unwrap()));
+// marks every note played in the window with how it compares to the note it was trying to play
+// the marker is drawn where the note was played and a line connects it to where it should have been played
+#[allow(clippy::too_many_arguments)]
+fn draw_practice_feedback(
+    canvas: &mut dyn Canvas,
+    staff: &Staff,
+    theme: &Theme,
+    music: &PianoPhase,
+    practice: &PracticeSession,
+    window_duration: Rational32,
+    note_horiz_space: f32,
+    current_time: f32,
+) {
+    let part = music.part(practice.part_number);
+    let marker_y = match practice.part_number {
+        PartNumber::Part1 => STEM_ABOVE_Y - 2.0,
+        PartNumber::Part2 => STEM_BELOW_Y + 2.0,
+    };
+    let notes_left = CLEF_OFFSET + CLEF_WIDTH;
+
+    // same as the positioning of the notes in draw_in_sync_staff
+    let time_to_x = |time: f32, measure_time: f32| {
+        let base_speed_segment = &music.part1.segments[music.part1.find_segment_for_time(measure_time)?];
+        let base_speed_measure = base_speed_segment.find_measure(measure_time);
+        Some(remap(
+            time,
+            base_speed_measure.start_time.to_f32().unwrap(),
+            base_speed_measure.end_time.to_f32().unwrap(),
+            notes_left,
+            base_speed_segment.single_measure_duration().to_f32().unwrap() * note_horiz_space + notes_left,
+        ))
+    };
 
-                let note_color = if note.time == notes.last().unwrap().time { colors::IMPORTANT_FOREGROUND_COLOR } else { colors::FOREGROUND_COLOR }
-                    .set_a(note.volume * note_fade);
-                let beam_color = colors::FOREGROUND_COLOR.set_a(note.volume * note_fade);
+    let window_start = current_time - window_duration.to_f32().unwrap();
+    let played_start = practice.played.partition_point(|played| played.time < window_start);
+    for played in &practice.played[played_start..] {
+        if played.time > current_time {
+            break;
+        }
 
-                staff.draw_note(note_x, note.pitch, note_color, beam_color, stem_end_y, 2, left_beam_x, right_beam_x);
-            }
+        let color = match played.judgement {
+            Judgement::OnTime => theme.on_time,
+            Judgement::Early => theme.early,
+            Judgement::Late => theme.late,
+            Judgement::WrongPitch | Judgement::Extra => theme.wrong_pitch,
         };
+        let Some(played_x) = time_to_x(played.time, played.time) else { continue };
+        let (played_position, _) = staff.calculate_position(played_x, marker_y);
+
+        if let Some(target) = played.target {
+            // the target is positioned relative to the measure that the note was played in so that the line does not jump across the staff
+            if let Some(target_x) = time_to_x(part.flattened[target].time.to_f32().unwrap(), played.time) {
+                let (target_position, _) = staff.calculate_position(target_x, marker_y);
+                canvas.draw_line(target_position.x, target_position.y, played_position.x, played_position.y, staff.staff_space * 0.2, color);
+            }
+        }
 
-        draw_past_notes(&staff, &music.part1, window_length, STEM_ABOVE_Y);
-        draw_past_notes(&staff, &music.part2, window_length, STEM_BELOW_Y);
+        canvas.draw_circle(played_position.x, played_position.y, staff.staff_space * 0.4, color);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_out_of_sync_staff(
+    canvas: &mut dyn Canvas,
     font: &notation::Font,
+    theme: &Theme,
     music: &PianoPhase,
     window: Rect,
     current_time: f32,
@@ -292,14 +526,13 @@ fn draw_out_of_sync_staff(
 ) {
     // TODO: this code was copied and pasted from draw_in_sync_staff and duplicates a lot of it
     // TODO: this code also duplicates a lot of draw_wheel
-    let staff_space = (window.w / 120.0) as u16;
-    let staff_1_top = window.y + window.h * 0.3 - staff_space as f32 * 2.0; // center the staff vertically
-    let staff_2_top = window.y + window.h * 0.7 - staff_space as f32 * 2.0; // center the staff vertically
+    let staff_space = f32::min(window.w / 120.0, window.h / 24.0);
+    let staff_1_top = window.y + window.h * 0.3 - staff_space * 2.0; // center the staff vertically
+    let staff_2_top = window.y + window.h * 0.7 - staff_space * 2.0; // center the staff vertically
 
-    let go = |segment: &Segment, staff_top: f32, hairpin_y: f32| {
+    let go = |canvas: &mut dyn Canvas, segment: &Segment, part_colors: &PartColors, staff_top: f32, hairpin_y: f32| {
         let note_horiz_space = 4.0;
-        let staff_width =
-            (segment.pattern.0.len() as f32 * note_horiz_space + CLEF_OFFSET + CLEF_WIDTH + REPEAT_WIDTH + REPEAT_WIDTH) * staff_space as f32;
+        let staff_width = (segment.pattern.0.len() as f32 * note_horiz_space + CLEF_OFFSET + CLEF_WIDTH + REPEAT_WIDTH + REPEAT_WIDTH) * staff_space;
         let staff_left = window.x + window.w * 0.5 - staff_width * 0.5;
 
         let staff = Staff::new(font, StaffPosition::Straight { top: staff_top, left: staff_left, right: staff_left + staff_width }, staff_space);
@@ -313,27 +546,27 @@ fn draw_out_of_sync_staff(
             remap(current_time, current_measure.start_time.to_f32().unwrap(), current_measure.end_time.to_f32().unwrap(), 0.0, 1.0);
         let current_note_index = (offset_in_measure * pattern_len as f32).floor() as usize;
 
-        let current_dynamic = segment.dynamic.interpolate(offset_in_segment);
+        let current_dynamic = segment.dynamic.interpolate_relative(offset_in_segment);
 
-        let normal_note_color = colors::FOREGROUND_COLOR.modify_a(|a| a * current_dynamic);
-        let highlighted_note_color = colors::IMPORTANT_FOREGROUND_COLOR.modify_a(|a| a * current_dynamic);
-        let highlight_color = colors::HIGHLIGHT_COLOR.modify_a(|a| a * current_dynamic);
+        let normal_note_color = part_colors.notes.modify_a(|a| a * current_dynamic);
+        let highlighted_note_color = part_colors.current_note.modify_a(|a| a * current_dynamic);
+        let highlight_color = theme.highlight.modify_a(|a| a * current_dynamic);
 
-        staff.draw(colors::FOREGROUND_COLOR);
+        staff.draw(canvas, theme.foreground);
 
-        staff.draw_treble_clef(CLEF_OFFSET, colors::FOREGROUND_COLOR);
+        staff.draw_treble_clef(canvas, CLEF_OFFSET, theme.foreground);
 
         let notes_start_x = CLEF_OFFSET + CLEF_WIDTH + REPEAT_WIDTH;
         let last_note_x_position = notes_start_x + pattern_len as f32 * note_horiz_space;
 
-        staff.draw_starting_repeat_sign(notes_start_x - REPEAT_WIDTH * 0.5, colors::FOREGROUND_COLOR);
-        staff.draw_ending_repeat_sign(last_note_x_position + REPEAT_WIDTH * 0.5, colors::FOREGROUND_COLOR);
+        staff.draw_starting_repeat_sign(canvas, notes_start_x - REPEAT_WIDTH * 0.5, theme.foreground);
+        staff.draw_ending_repeat_sign(canvas, last_note_x_position + REPEAT_WIDTH * 0.5, theme.foreground);
 
-        draw_rectangle(
-            staff_left + notes_start_x * staff.staff_space as f32,
+        canvas.draw_rectangle(
+            staff_left + notes_start_x * staff.staff_space,
             staff_top,
-            lerp(0.0, last_note_x_position - notes_start_x, offset_in_measure) * staff.staff_space as f32,
-            staff.staff_height as f32,
+            lerp(0.0, last_note_x_position - notes_start_x, offset_in_measure) * staff.staff_space,
+            staff.staff_height,
             highlight_color,
         );
 
@@ -359,20 +592,93 @@ fn draw_out_of_sync_staff(
 
             let note_color = if note_i == current_note_index { highlighted_note_color } else { normal_note_color };
 
-            staff.draw_note(note_x, note.pitch, note_color, normal_note_color, stem_end_y, 2, beam_left, beam_right)
+            staff.draw_note(canvas, note_x, note.pitch, note_color, normal_note_color, stem_end_y, 2, beam_left, beam_right);
+            if note.accent {
+                staff.draw_accent(canvas, note_x, stem_end_y, note_color);
+            }
         }
 
         match segment.dynamic {
-            crate::music::Dynamic::Crescendo => staff.draw_crescendo(hairpin_y, notes_start_x, last_note_x_position, colors::FOREGROUND_COLOR),
-            crate::music::Dynamic::Decrescendo => staff.draw_decrescendo(hairpin_y, notes_start_x, last_note_x_position, colors::FOREGROUND_COLOR),
-            crate::music::Dynamic::Flat | crate::music::Dynamic::Silent => {}
+            crate::music::Dynamic::Hairpin { from, to, curve: _ } => {
+                staff.draw_hairpin(canvas, hairpin_y, notes_start_x, last_note_x_position, theme.foreground, from, to)
+            }
+            crate::music::Dynamic::Constant(level) => staff.draw_dynamic(canvas, notes_start_x, hairpin_y, level, theme.foreground),
+            crate::music::Dynamic::Silent => {}
         }
     };
 
     if let Some(part1_segment_index) = part1_segment_index {
-        go(&music.part1.segments[part1_segment_index], staff_1_top, DYNAMICS_Y);
+        go(canvas, &music.part1.segments[part1_segment_index], &theme.part1, staff_1_top, DYNAMICS_Y);
     }
     if let Some(part2_segment_index) = part2_segment_index {
-        go(&music.part2.segments[part2_segment_index], staff_2_top, DYNAMICS_Y);
+        go(canvas, &music.part2.segments[part2_segment_index], &theme.part2, staff_2_top, DYNAMICS_Y);
     }
 }
+
+// the notes of both parts combined into one staff, over the current measure of part 1
+// the notes of the highest line that the parts make together have their stems up, and unisons are highlighted
+fn draw_resultant_staff(canvas: &mut dyn Canvas, font: &notation::Font, theme: &Theme, music: &PianoPhase, window: Rect, current_time: f32) {
+    let Some(resultant) = Resultant::at_time(music, current_time) else {
+        return;
+    };
+
+    let staff_space = f32::min(window.w / 120.0, window.h / 12.0);
+    let staff_top = window.y + window.h * 0.5 - staff_space * 2.0; // center the staff vertically
+    let note_horiz_space = 4.0;
+    let measure_length = (resultant.end_time - resultant.start_time).to_f32().unwrap();
+    let staff_width = (measure_length * note_horiz_space + CLEF_OFFSET + CLEF_WIDTH + REPEAT_WIDTH + REPEAT_WIDTH) * staff_space;
+    let staff_left = window.x + window.w * 0.5 - staff_width * 0.5;
+
+    let staff = Staff::new(font, StaffPosition::Straight { top: staff_top, left: staff_left, right: staff_left + staff_width }, staff_space);
+
+    staff.draw(canvas, theme.foreground);
+    staff.draw_treble_clef(canvas, CLEF_OFFSET, theme.foreground);
+
+    let notes_start_x = CLEF_OFFSET + CLEF_WIDTH + REPEAT_WIDTH;
+    let last_note_x_position = notes_start_x + measure_length * note_horiz_space;
+
+    staff.draw_starting_repeat_sign(canvas, notes_start_x - REPEAT_WIDTH * 0.5, theme.foreground);
+    staff.draw_ending_repeat_sign(canvas, last_note_x_position + REPEAT_WIDTH * 0.5, theme.foreground);
+
+    let time_to_x =
+        |time: f32| remap(time, resultant.start_time.to_f32().unwrap(), resultant.end_time.to_f32().unwrap(), notes_start_x, last_note_x_position);
+
+    canvas.draw_rectangle(
+        staff_left + notes_start_x * staff.staff_space,
+        staff_top,
+        (time_to_x(current_time) - notes_start_x) * staff.staff_space,
+        staff.staff_height,
+        theme.highlight,
+    );
+
+    let upper_line = resultant.upper_emergent_line();
+    for event in &resultant.events {
+        let note_x = time_to_x(event.time.to_f32().unwrap());
+        let is_unison = event.is_unison();
+
+        if is_unison {
+            let (highlight_position, _) = staff.calculate_position(note_x - 0.5, -1.0);
+            canvas.draw_rectangle(
+                highlight_position.x,
+                highlight_position.y,
+                2.0 * staff.staff_space,
+                staff.staff_height + 2.0 * staff.staff_space,
+                theme.unison.set_a(0.3),
+            );
+        }
+
+        for note in &event.notes {
+            let (stem_end_y, color) = if Some(note.line) == upper_line {
+                (STEM_ABOVE_Y, theme.important_foreground)
+            } else {
+                (STEM_BELOW_Y, theme.part(note.part_number).identifying)
+            };
+            let color = if is_unison { theme.unison } else { color }.set_a(note.volume);
+
+            staff.draw_note(canvas, note_x, note.pitch, color, color, stem_end_y, 2, None, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
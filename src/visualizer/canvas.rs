@@ -0,0 +1,137 @@
+use std::{cell::OnceCell, path::PathBuf};
+
+use macroquad::{color::Color, text::TextDimensions};
+
+use crate::error::AssetError;
+
+// everything that the visualizer draws goes through a canvas, so that the same drawing code can draw onto the window or into an image
+// the shapes are the same as the macroquad functions with the same names
+pub trait Canvas {
+    fn clear(&mut self, color: Color);
+    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color);
+    fn draw_rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color);
+    // the lines are drawn inside of the rectangle and are half as thick as the thickness
+    fn draw_rectangle_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color);
+    fn draw_circle(&mut self, x: f32, y: f32, r: f32, color: Color);
+    // the line goes outwards from the radius
+    fn draw_circle_lines(&mut self, x: f32, y: f32, r: f32, thickness: f32, color: Color);
+    // the rotation and the arc are in degrees clockwise from the right, and the arc goes outwards from the radius
+    #[allow(clippy::too_many_arguments)]
+    fn draw_arc(&mut self, x: f32, y: f32, radius: f32, rotation: f32, thickness: f32, arc: f32, color: Color);
+    // the text starts at x and its baseline is at y
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, params: &TextParams) -> TextDimensions;
+    fn measure_text(&self, text: &str, params: &TextParams) -> TextDimensions;
+}
+
+// a font that can be drawn onto any canvas
+pub struct Typeface {
+    pub path: PathBuf,
+    pub font: fontdue::Font,
+    bytes: Vec<u8>,
+    // the window can only load fonts once it is open, so the font for it is only loaded when the text is first drawn onto it
+    screen_font: OnceCell<Option<macroquad::text::Font>>,
+}
+
+#[derive(Clone, Copy)]
+pub struct TextParams<'a> {
+    pub typeface: &'a Typeface,
+    pub font_size: u16,
+    // the glyphs are rasterized at the font size and then scaled
+    pub font_scale: f32,
+    // in radians clockwise around the start of the baseline
+    pub rotation: f32,
+    pub color: Color,
+}
+
+// draws onto the window, or onto the render target of the current camera
+pub struct Screen;
+
+impl Typeface {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Typeface, AssetError> {
+        let path = path.into();
+        let bytes = std::fs::read(&path).map_err(|source| AssetError::Open { path: path.clone(), source })?;
+        let font = fontdue::Font::from_bytes(bytes.as_slice(), fontdue::FontSettings::default())
+            .map_err(|message| AssetError::InvalidFont { path: path.clone(), message })?;
+        Ok(Typeface { path, font, bytes, screen_font: OnceCell::new() })
+    }
+
+    // the font file, for embedding it into other files
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn screen_font(&self) -> Option<&macroquad::text::Font> {
+        // the bytes were already parsed by fontdue, which is what macroquad uses to parse them too
+        self.screen_font.get_or_init(|| macroquad::text::load_ttf_font_from_bytes(&self.bytes).ok()).as_ref()
+    }
+
+    // the same measurements as macroquad::text::measure_text, without needing a window
+    pub fn measure_text(&self, text: &str, font_size: u16, font_scale: f32) -> TextDimensions {
+        if text.is_empty() {
+            return TextDimensions::default();
+        }
+        let (mut width, mut min_y, mut max_y) = (0.0, f32::MAX, f32::MIN);
+        for character in text.chars() {
+            let metrics = self.font.metrics(character, font_size as f32);
+            width += metrics.advance_width * font_scale;
+            min_y = min_y.min(metrics.ymin as f32 * font_scale);
+            max_y = max_y.max((metrics.ymin as f32 + metrics.height as f32) * font_scale);
+        }
+        TextDimensions { width, height: max_y - min_y, offset_y: max_y }
+    }
+}
+
+impl<'a> TextParams<'a> {
+    pub fn new(typeface: &'a Typeface, font_size: u16, color: Color) -> TextParams<'a> {
+        TextParams { typeface, font_size, font_scale: 1.0, rotation: 0.0, color }
+    }
+}
+
+impl Canvas for Screen {
+    fn clear(&mut self, color: Color) {
+        macroquad::window::clear_background(color);
+    }
+    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        macroquad::shapes::draw_line(x1, y1, x2, y2, thickness, color);
+    }
+    fn draw_rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        macroquad::shapes::draw_rectangle(x, y, w, h, color);
+    }
+    fn draw_rectangle_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        macroquad::shapes::draw_rectangle_lines(x, y, w, h, thickness, color);
+    }
+    fn draw_circle(&mut self, x: f32, y: f32, r: f32, color: Color) {
+        macroquad::shapes::draw_circle(x, y, r, color);
+    }
+    fn draw_circle_lines(&mut self, x: f32, y: f32, r: f32, thickness: f32, color: Color) {
+        macroquad::shapes::draw_circle_lines(x, y, r, thickness, color);
+    }
+    fn draw_arc(&mut self, x: f32, y: f32, radius: f32, rotation: f32, thickness: f32, arc: f32, color: Color) {
+        // enough sides for a whole circle that the arcs look round at the sizes they are drawn at
+        macroquad::shapes::draw_arc(x, y, 56, radius, rotation, thickness, arc, color);
+    }
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, params: &TextParams) -> TextDimensions {
+        let Some(font) = params.typeface.screen_font() else {
+            return TextDimensions::default();
+        };
+        macroquad::text::draw_text_ex(
+            text,
+            x,
+            y,
+            macroquad::text::TextParams {
+                font: Some(font),
+                font_size: params.font_size,
+                font_scale: params.font_scale,
+                font_scale_aspect: 1.0,
+                rotation: params.rotation,
+                color: params.color,
+            },
+        )
+    }
+    fn measure_text(&self, text: &str, params: &TextParams) -> TextDimensions {
+        match params.typeface.screen_font() {
+            Some(font) => macroquad::text::measure_text(text, Some(font), params.font_size, params.font_scale),
+            None => TextDimensions::default(),
+        }
+    }
+}
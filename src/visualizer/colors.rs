@@ -1,20 +1,178 @@
+use std::{fs::File, io::BufReader};
+
 use macroquad::color::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::{error::AssetError, music::PartNumber, util::lerp};
 
-macro_rules! color_constant {
-    ($name:ident, $hex_value: literal) => {
-        pub const $name: Color = Color::new(
+macro_rules! hex_color {
+    ($hex_value: literal) => {
+        Color::new(
             ($hex_value >> 24) as f32 / 255.0,
             ($hex_value >> 16 & 0xff) as f32 / 255.0,
             ($hex_value >> 8 & 0xff) as f32 / 255.0,
             ($hex_value & 0xff) as f32 / 255.0,
-        );
+        )
     };
 }
 
-color_constant!(BACKGROUND_COLOR, 0xf2f2f8ffu32);
-color_constant!(FOREGROUND_COLOR, 0x0f0142ffu32);
-color_constant!(HIGHLIGHT_COLOR, 0xac9eeb60u32);
-color_constant!(IMPORTANT_FOREGROUND_COLOR, 0x8e00faffu32);
+// theme files are json objects with the same fields as this struct, and colors are written as "#rrggbb" or "#rrggbbaa"
+// any field that is left out is taken from the light theme, also the single colors of a part
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "deserialize_color")]
+    pub background: Color,
+    // staff lines, text, and everything else that does not belong to a part
+    #[serde(deserialize_with = "deserialize_color")]
+    pub foreground: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub important_foreground: Color,
+    // the progress through the current measure
+    #[serde(deserialize_with = "deserialize_color")]
+    pub highlight: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub unison: Color,
+
+    #[serde(deserialize_with = "deserialize_part1_colors")]
+    pub part1: PartColors,
+    #[serde(deserialize_with = "deserialize_part2_colors")]
+    pub part2: PartColors,
+
+    #[serde(deserialize_with = "deserialize_color")]
+    pub on_time: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub early: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub late: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub wrong_pitch: Color,
+}
+
+#[derive(Clone, Debug)]
+pub struct PartColors {
+    // the notes on the wheel and the staves
+    pub notes: Color,
+    pub current_note: Color,
+    // tells the parts apart in the views that draw both parts together
+    pub identifying: Color,
+}
+
+// the colors of a part as written in a theme file, where any color that is left out is taken from the same part of the light theme
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartColorsFile {
+    #[serde(default, deserialize_with = "deserialize_some_color")]
+    notes: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_some_color")]
+    current_note: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_some_color")]
+    identifying: Option<Color>,
+}
+
+pub const LIGHT_THEME: Theme = Theme {
+    background: hex_color!(0xf2f2f8ffu32),
+    foreground: hex_color!(0x0f0142ffu32),
+    important_foreground: hex_color!(0x8e00faffu32),
+    highlight: hex_color!(0xac9eeb60u32),
+    unison: hex_color!(0xf2b705ffu32),
+    part1: PartColors { notes: hex_color!(0x0f0142ffu32), current_note: hex_color!(0x8e00faffu32), identifying: hex_color!(0x3b2fc9ffu32) },
+    part2: PartColors { notes: hex_color!(0x0f0142ffu32), current_note: hex_color!(0x8e00faffu32), identifying: hex_color!(0xc92f8effu32) },
+    on_time: hex_color!(0x1c9e4bffu32),
+    early: hex_color!(0x1f6fd1ffu32),
+    late: hex_color!(0xe07b00ffu32),
+    wrong_pitch: hex_color!(0xd11f2fffu32),
+};
+
+pub const DARK_THEME: Theme = Theme {
+    background: hex_color!(0x0d0b17ffu32),
+    foreground: hex_color!(0xe4e1f5ffu32),
+    important_foreground: hex_color!(0xc58cffffu32),
+    highlight: hex_color!(0x5b4bb060u32),
+    unison: hex_color!(0xf2c12effu32),
+    part1: PartColors { notes: hex_color!(0xd6d2ffffu32), current_note: hex_color!(0xc58cffffu32), identifying: hex_color!(0x8a80ffffu32) },
+    part2: PartColors { notes: hex_color!(0xffd6ecffu32), current_note: hex_color!(0xff8cc6ffu32), identifying: hex_color!(0xff6fbcffu32) },
+    on_time: hex_color!(0x4fd17effu32),
+    early: hex_color!(0x5aa2ffffu32),
+    late: hex_color!(0xffa23affu32),
+    wrong_pitch: hex_color!(0xff5a66ffu32),
+};
+
+pub const HIGH_CONTRAST_THEME: Theme = Theme {
+    background: hex_color!(0x000000ffu32),
+    foreground: hex_color!(0xffffffffu32),
+    important_foreground: hex_color!(0xffff00ffu32),
+    highlight: hex_color!(0x0050ff90u32),
+    unison: hex_color!(0x00ff00ffu32),
+    part1: PartColors { notes: hex_color!(0xffffffffu32), current_note: hex_color!(0xffff00ffu32), identifying: hex_color!(0x00ffffffu32) },
+    part2: PartColors { notes: hex_color!(0xffffffffu32), current_note: hex_color!(0xffff00ffu32), identifying: hex_color!(0xff00ffffu32) },
+    on_time: hex_color!(0x00ff00ffu32),
+    early: hex_color!(0x00ffffffu32),
+    late: hex_color!(0xff8000ffu32),
+    wrong_pitch: hex_color!(0xff0000ffu32),
+};
+
+impl Default for Theme {
+    fn default() -> Self {
+        LIGHT_THEME
+    }
+}
+
+impl Theme {
+    // either the name of a built in theme ("light", "dark", or "high-contrast") or the path to a theme file
+    pub fn load(name_or_path: &str) -> Result<Theme, AssetError> {
+        match name_or_path {
+            "light" => Ok(LIGHT_THEME),
+            "dark" => Ok(DARK_THEME),
+            "high-contrast" => Ok(HIGH_CONTRAST_THEME),
+            path => {
+                let file = File::open(path).map_err(|source| AssetError::Open { path: path.into(), source })?;
+                serde_json::from_reader(BufReader::new(file)).map_err(|source| AssetError::InvalidJson { path: path.into(), source })
+            }
+        }
+    }
+
+    pub fn part(&self, part_number: PartNumber) -> &PartColors {
+        match part_number {
+            PartNumber::Part1 => &self.part1,
+            PartNumber::Part2 => &self.part2,
+        }
+    }
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let string = String::deserialize(deserializer)?;
+    let invalid = || serde::de::Error::custom(format!("invalid color {string:?}, expected \"#rrggbb\" or \"#rrggbbaa\""));
+
+    let digits = string.strip_prefix('#').ok_or_else(invalid)?;
+    let value = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+    match digits.len() {
+        6 => Ok(Color::from_rgba((value >> 16) as u8, (value >> 8) as u8, value as u8, 0xff)),
+        8 => Ok(Color::from_rgba((value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8)),
+        _ => Err(invalid()),
+    }
+}
+
+fn deserialize_some_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    deserialize_color(deserializer).map(Some)
+}
+
+fn deserialize_part1_colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PartColors, D::Error> {
+    deserialize_part_colors(deserializer, &LIGHT_THEME.part1)
+}
+
+fn deserialize_part2_colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PartColors, D::Error> {
+    deserialize_part_colors(deserializer, &LIGHT_THEME.part2)
+}
+
+fn deserialize_part_colors<'de, D: Deserializer<'de>>(deserializer: D, light: &PartColors) -> Result<PartColors, D::Error> {
+    let file = PartColorsFile::deserialize(deserializer)?;
+    Ok(PartColors {
+        notes: file.notes.unwrap_or(light.notes),
+        current_note: file.current_note.unwrap_or(light.current_note),
+        identifying: file.identifying.unwrap_or(light.identifying),
+    })
+}
 
 pub trait ChangeAlpha {
     fn set_a(&self, a: f32) -> Self;
@@ -29,3 +187,7 @@ impl ChangeAlpha for Color {
         Color { a: f(self.a), ..*self }
     }
 }
+
+pub fn mix(a: Color, b: Color, t: f32) -> Color {
+    Color::new(lerp(a.r, b.r, t), lerp(a.g, b.g, t), lerp(a.b, b.b, t), lerp(a.a, b.a, t))
+}
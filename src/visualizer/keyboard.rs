@@ -0,0 +1,95 @@
+use macroquad::{color::Color, math::Rect};
+use num_traits::ToPrimitive;
+
+use crate::{
+    music::{Hand, Part, PianoPhase},
+    visualizer::{
+        canvas::{Canvas, TextParams, Typeface},
+        colors::{self, Theme},
+    },
+};
+
+const BLACK_KEY_WIDTH: f32 = 0.6;
+const BLACK_KEY_HEIGHT: f32 = 0.6;
+
+// a piano keyboard covering the range of the music, with the keys that are currently sounding lit up in the color of the part playing them
+// if both parts are playing the same key, part 1 lights up the left half of the key and part 2 lights up the right half
+pub fn draw_keyboard(canvas: &mut dyn Canvas, text_font: &Typeface, theme: &Theme, music: &PianoPhase, window: Rect, current_time: f32) {
+    let all_notes = || music.part1.flattened.iter().chain(&music.part2.flattened);
+    let (Some(lowest_pitch), Some(highest_pitch)) = (all_notes().map(|n| n.pitch).min(), all_notes().map(|n| n.pitch).max()) else {
+        return;
+    };
+    // the keyboard should start and end on a white key
+    let lowest_pitch = if is_black_key(lowest_pitch) { lowest_pitch - 1 } else { lowest_pitch };
+    let highest_pitch = if is_black_key(highest_pitch) { highest_pitch + 1 } else { highest_pitch };
+
+    let white_keys_before = |pitch: u8| (lowest_pitch..pitch).filter(|p| !is_black_key(*p)).count() as f32;
+    let white_key_width = window.w / (white_keys_before(highest_pitch) + 1.0);
+    let line_thickness = white_key_width * 0.03;
+
+    let key_rect = |pitch: u8| {
+        if is_black_key(pitch) {
+            Rect::new(
+                window.x + (white_keys_before(pitch) - BLACK_KEY_WIDTH * 0.5) * white_key_width,
+                window.y,
+                white_key_width * BLACK_KEY_WIDTH,
+                window.h * BLACK_KEY_HEIGHT,
+            )
+        } else {
+            Rect::new(window.x + white_keys_before(pitch) * white_key_width, window.y, white_key_width, window.h)
+        }
+    };
+
+    let sounding = |part: &Part| -> Vec<(u8, f32, Hand)> {
+        let notes = &part.flattened;
+        let start = notes.partition_point(|n| (n.time + n.length).to_f32().unwrap() <= current_time);
+        let end = notes.partition_point(|n| n.time.to_f32().unwrap() <= current_time);
+        notes[start..end].iter().map(|n| (n.pitch, n.relative_volume(part), n.hand)).collect()
+    };
+    let part1_sounding = sounding(&music.part1);
+    let part2_sounding = sounding(&music.part2);
+
+    let draw_key = |canvas: &mut dyn Canvas, pitch: u8| {
+        let rect = key_rect(pitch);
+        let unlit_color = if is_black_key(pitch) { theme.foreground } else { theme.background };
+        canvas.draw_rectangle(rect.x, rect.y, rect.w, rect.h, unlit_color);
+
+        let part1_note = part1_sounding.iter().find(|(p, _, _)| *p == pitch);
+        let part2_note = part2_sounding.iter().find(|(p, _, _)| *p == pitch);
+        let lit = [(part1_note, theme.part1.identifying), (part2_note, theme.part2.identifying)];
+        let lit_count = lit.iter().filter(|(note, _)| note.is_some()).count();
+
+        let mut lit_x = rect.x;
+        for (note, part_color) in lit {
+            if let Some((_, volume, hand)) = note {
+                let lit_w = rect.w / lit_count as f32;
+                // quieter notes are drawn closer to the color of the unlit key
+                canvas.draw_rectangle(lit_x, rect.y, lit_w, rect.h, colors::mix(unlit_color, part_color, 0.25 + 0.75 * volume));
+                draw_hand_label(canvas, text_font, *hand, Rect::new(lit_x, rect.y, lit_w, rect.h), unlit_color);
+                lit_x += lit_w;
+            }
+        }
+
+        canvas.draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, line_thickness, theme.foreground);
+    };
+
+    // the black keys are drawn on top of the white keys
+    (lowest_pitch..=highest_pitch).filter(|p| !is_black_key(*p)).for_each(|pitch| draw_key(canvas, pitch));
+    (lowest_pitch..=highest_pitch).filter(|p| is_black_key(*p)).for_each(|pitch| draw_key(canvas, pitch));
+}
+
+// marks which hand is playing a key with an L or R at the bottom of the key
+fn draw_hand_label(canvas: &mut dyn Canvas, text_font: &Typeface, hand: Hand, key: Rect, color: Color) {
+    let label = match hand {
+        Hand::Left => "L",
+        Hand::Right => "R",
+    };
+    let font_size = (key.w * 0.6) as u16;
+    let params = TextParams::new(text_font, font_size, color);
+    let dimensions = canvas.measure_text(label, &params);
+    canvas.draw_text(label, key.x + (key.w - dimensions.width) * 0.5, key.y + key.h - dimensions.height * 0.5, &params);
+}
+
+pub fn is_black_key(pitch: u8) -> bool {
+    matches!(pitch % 12, 1 | 3 | 6 | 8 | 10)
+}
@@ -0,0 +1,117 @@
+use macroquad::math::Rect;
+
+use crate::music::PartNumber;
+
+// which panels are drawn and where
+// panels that are not in the layout are not drawn, and panels are drawn in order so later panels are drawn on top of earlier ones
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    pub panels: &'static [PanelPlacement],
+    // the width and the height the panels are placed for, as the smallest whole numbers with the same ratio
+    pub aspect_ratio: (u32, u32),
+}
+
+impl Layout {
+    // the height of a drawing with this layout, so that the size of the output follows the layout
+    pub const fn height_for_width(&self, width: u32) -> u32 {
+        width * self.aspect_ratio.1 / self.aspect_ratio.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Panel {
+    StatusText,
+    PhaseDiagram,
+    Wheel(PartNumber),
+    Keyboard,
+    InSyncStaff,
+    OutOfSyncStaves,
+    ResultantStaff,
+    PianoRoll,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PanelPlacement {
+    pub panel: Panel,
+    pub area: Area,
+}
+
+// a rectangle measured in fractions of the size of the drawing, so that the same layout works at any resolution with the same aspect ratio
+#[derive(Clone, Copy, Debug)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Area {
+    pub fn to_rect(self, width: f32, height: f32) -> Rect {
+        Rect::new(self.x * width, self.y * height, self.w * width, self.h * height)
+    }
+}
+
+const fn place(panel: Panel, x: f32, y: f32, w: f32, h: f32) -> PanelPlacement {
+    PanelPlacement { panel, area: Area { x, y, w, h } }
+}
+
+// the wheels and the staves, for square output
+pub const SQUARE: Layout = Layout {
+    aspect_ratio: (1, 1),
+    panels: &[
+        place(Panel::StatusText, 0.0, 0.0, 0.45, 0.15),
+        place(Panel::PhaseDiagram, 0.45, 0.02, 0.52, 0.09),
+        place(Panel::Wheel(PartNumber::Part1), 0.0, 0.1, 0.5, 0.4),
+        place(Panel::Wheel(PartNumber::Part2), 0.5, 0.1, 0.5, 0.4),
+        place(Panel::Keyboard, 0.42, 0.24, 0.16, 0.12),
+        place(Panel::InSyncStaff, 0.0, 0.48, 1.0, 0.15),
+        place(Panel::OutOfSyncStaves, 0.0, 0.63, 1.0, 0.25),
+        place(Panel::ResultantStaff, 0.0, 0.88, 1.0, 0.12),
+    ],
+};
+
+// the wheels and the staves, for 1920x1080 video
+pub const LANDSCAPE: Layout = Layout {
+    aspect_ratio: (16, 9),
+    panels: &[
+        place(Panel::StatusText, 0.0, 0.0, 0.4, 0.18),
+        place(Panel::PhaseDiagram, 0.42, 0.02, 0.56, 0.11),
+        place(Panel::Wheel(PartNumber::Part1), 0.0, 0.15, 0.3, 0.45),
+        place(Panel::Wheel(PartNumber::Part2), 0.7, 0.15, 0.3, 0.45),
+        place(Panel::Keyboard, 0.35, 0.3, 0.3, 0.15),
+        place(Panel::InSyncStaff, 0.0, 0.58, 1.0, 0.15),
+        place(Panel::OutOfSyncStaves, 0.0, 0.72, 1.0, 0.28),
+    ],
+};
+
+// the wheels stacked on top of each other, for 1080x1920 vertical video
+pub const VERTICAL: Layout = Layout {
+    aspect_ratio: (9, 16),
+    panels: &[
+        place(Panel::StatusText, 0.0, 0.0, 1.0, 0.07),
+        place(Panel::PhaseDiagram, 0.05, 0.07, 0.9, 0.06),
+        place(Panel::Wheel(PartNumber::Part1), 0.0, 0.14, 1.0, 0.22),
+        place(Panel::Wheel(PartNumber::Part2), 0.0, 0.36, 1.0, 0.22),
+        place(Panel::Keyboard, 0.2, 0.59, 0.6, 0.06),
+        place(Panel::InSyncStaff, 0.0, 0.66, 1.0, 0.1),
+        place(Panel::OutOfSyncStaves, 0.0, 0.76, 1.0, 0.16),
+        place(Panel::ResultantStaff, 0.0, 0.91, 1.0, 0.09),
+    ],
+};
+
+// a scrolling piano roll instead of notation, for square output
+pub const PIANO_ROLL: Layout = Layout {
+    aspect_ratio: (1, 1),
+    panels: &[
+        place(Panel::StatusText, 0.0, 0.0, 0.45, 0.15),
+        place(Panel::PhaseDiagram, 0.45, 0.02, 0.52, 0.09),
+        place(Panel::PianoRoll, 0.0, 0.15, 1.0, 0.6),
+        place(Panel::Keyboard, 0.1, 0.78, 0.8, 0.18),
+    ],
+};
+
+// only the two wheels side by side, for still images of the phasing
+pub const WHEELS: Layout = Layout {
+    aspect_ratio: (2, 1),
+    panels: &[place(Panel::Wheel(PartNumber::Part1), 0.0, 0.0, 0.5, 1.0), place(Panel::Wheel(PartNumber::Part2), 0.5, 0.0, 0.5, 1.0)],
+};
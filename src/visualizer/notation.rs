@@ -1,19 +1,22 @@
-use std::{fs::File, io::BufReader};
+use std::{fs::File, io::BufReader, path::PathBuf};
 
-use macroquad::{
-    color::Color,
-    math::Vec2,
-    shapes::{draw_arc, draw_circle_lines, draw_line},
-    text::{draw_text_ex, TextParams},
-};
+use macroquad::{color::Color, math::Vec2};
 use num_traits::FloatConst;
 use smufl::{Coord, Glyph, Metadata, StaffSpaces};
 
-use crate::util::circle_coord;
+use crate::{
+    error::AssetError,
+    music::{DynamicLevel, DRAWABLE_PITCHES},
+    util::circle_coord,
+    visualizer::canvas::{Canvas, TextParams, Typeface},
+};
 
 pub const STEM_ABOVE_Y: f32 = -3.0;
 pub const STEM_BELOW_Y: f32 = 8.0;
 pub const DYNAMICS_Y: f32 = 10.0;
+// accents are drawn just outside of the staff, where they cannot run into the noteheads
+const ACCENT_ABOVE_Y: f32 = -1.5;
+const ACCENT_BELOW_Y: f32 = 5.5;
 
 pub const CLEF_OFFSET: f32 = 1.0;
 pub const CLEF_WIDTH: f32 = 6.0;
@@ -21,7 +24,7 @@ pub const REPEAT_WIDTH: f32 = 4.0; // TODO: this should depend on the font
 
 #[allow(clippy::manual_non_exhaustive)]
 pub struct Font {
-    pub font: macroquad::text::Font,
+    pub typeface: Typeface,
     pub metadata: smufl::Metadata,
     _dont_construct: (),
 }
@@ -30,8 +33,8 @@ pub struct Font {
 pub struct Staff<'font> {
     pub font: &'font Font,
     pub position: StaffPosition,
-    pub staff_space: u16,
-    pub staff_height: u16,
+    pub staff_space: f32,
+    pub staff_height: f32,
     _dont_construct: (),
 }
 pub enum StaffPosition {
@@ -40,59 +43,62 @@ pub enum StaffPosition {
 }
 
 impl Font {
-    pub async fn load_bravura() -> Result<Font, Box<dyn std::error::Error>> {
-        let file = File::open("data/bravura/redist/bravura_metadata.json")?;
+    pub fn load_bravura() -> Result<Font, AssetError> {
+        let metadata_path = PathBuf::from("data/bravura/redist/bravura_metadata.json");
+        let font_path = PathBuf::from("data/bravura/redist/otf/Bravura.otf");
+
+        let file = File::open(&metadata_path).map_err(|source| AssetError::Open { path: metadata_path.clone(), source })?;
         let reader = BufReader::new(file);
-        let metadata = Metadata::from_reader(reader)?;
-        let font = macroquad::text::load_ttf_font("data/bravura/redist/otf/Bravura.otf").await?;
-        Ok(Font { font, metadata, _dont_construct: () })
+        let metadata = Metadata::from_reader(reader).map_err(|source| AssetError::InvalidJson { path: metadata_path, source })?;
+        Ok(Font { typeface: Typeface::load(font_path)?, metadata, _dont_construct: () })
     }
 
-    pub fn make_text_params(&self, staff: &Staff, color: Color) -> TextParams {
-        TextParams { font: Some(&self.font), font_size: staff.staff_height, font_scale: 1.0, font_scale_aspect: 1.0, rotation: 0.0, color }
+    pub fn make_text_params(&self, staff: &Staff, color: Color) -> TextParams<'_> {
+        // the glyphs are rasterized at a whole number size and then scaled to exactly fill the staff
+        let font_size = staff.staff_height.round().max(1.0);
+        TextParams { font_scale: staff.staff_height / font_size, ..TextParams::new(&self.typeface, font_size as u16, color) }
     }
-    pub fn make_text_params_with_size(&self, font_size: u16, color: Color) -> TextParams {
-        TextParams { font: Some(&self.font), font_size, font_scale: 1.0, font_scale_aspect: 1.0, rotation: 0.0, color }
+    pub fn make_text_params_with_size(&self, font_size: u16, color: Color) -> TextParams<'_> {
+        TextParams::new(&self.typeface, font_size, color)
     }
 }
 
 impl<'font> Staff<'font> {
-    pub const fn new(font: &'font Font, staff_position: StaffPosition, staff_space: u16) -> Staff<'font> {
-        Staff { font, position: staff_position, staff_space, staff_height: staff_space * 4, _dont_construct: () }
+    pub const fn new(font: &'font Font, staff_position: StaffPosition, staff_space: f32) -> Staff<'font> {
+        Staff { font, position: staff_position, staff_space, staff_height: staff_space * 4.0, _dont_construct: () }
     }
 
     // for a circular staff, x is the angle around the circle
     pub fn calculate_position(&self, x: f32, y: f32) -> (Vec2, f32) {
         match self.position {
-            StaffPosition::Straight { top, left, right: _ } => {
-                (Vec2::new(left + x * self.staff_space as f32, top + y * self.staff_space as f32), 0.0)
-            }
+            StaffPosition::Straight { top, left, right: _ } => (Vec2::new(left + x * self.staff_space, top + y * self.staff_space), 0.0),
             StaffPosition::Circular { center_x, center_y, outer_radius } => {
-                (circle_coord(center_x, center_y, radius_for_y(outer_radius, y, self.staff_space as f32), x - f32::PI() / 2.0), x)
+                (circle_coord(center_x, center_y, radius_for_y(outer_radius, y, self.staff_space), x - f32::PI() / 2.0), x)
             }
         }
     }
 
-    pub fn draw(&self, color: Color) {
-        let line_thickness =
-            self.font.metadata.engraving_defaults.staff_line_thickness.unwrap_or(StaffSpaces(1.0 / 8.0)).0 as f32 * self.staff_space as f32;
+    pub fn draw(&self, canvas: &mut dyn Canvas, color: Color) {
+        let line_thickness = self.font.metadata.engraving_defaults.staff_line_thickness.unwrap_or(StaffSpaces(1.0 / 8.0)).0 as f32 * self.staff_space;
         match self.position {
             StaffPosition::Straight { top, left, right } => {
                 for i in 0..5 {
-                    let y = top + i as f32 * self.staff_space as f32;
-                    draw_line(left, y, right, y, line_thickness, color);
+                    let y = top + i as f32 * self.staff_space;
+                    canvas.draw_line(left, y, right, y, line_thickness, color);
                 }
             }
             StaffPosition::Circular { center_x, center_y, outer_radius } => {
                 for i in 0..5 {
-                    draw_circle_lines(center_x, center_y, outer_radius - i as f32 * self.staff_space as f32, line_thickness, color);
+                    canvas.draw_circle_lines(center_x, center_y, outer_radius - i as f32 * self.staff_space, line_thickness, color);
                 }
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_note(
         &self,
+        canvas: &mut dyn Canvas,
         x_coord_on_staff: f32,
         pitch: u8,
         note_color: Color,
@@ -108,31 +114,42 @@ impl<'font> Staff<'font> {
             #[allow(dead_code)]
             Flat,
         }
-        let (y_coord_on_staff, accidental) = match pitch {
-            64 => (4.0, Accidental::Natural),
-            69 => (2.5, Accidental::Natural),
-            66 => (3.5, Accidental::Sharp),
-            71 => (2.0, Accidental::Natural),
-            73 => (1.5, Accidental::Sharp),
-            74 => (1.0, Accidental::Natural),
-            76 => (0.5, Accidental::Natural),
-            _ => unimplemented!("{} not implemented", pitch),
+        // PianoPhase::validate rejects scores with pitches that are not on the staff, so these would only be drawn wrong
+        if !DRAWABLE_PITCHES.contains(&pitch) {
+            return;
+        }
+        // black keys are written as sharps, the same as in the score
+        let (step_in_octave, accidental) = match pitch % 12 {
+            0 => (0, Accidental::Natural),
+            1 => (0, Accidental::Sharp),
+            2 => (1, Accidental::Natural),
+            3 => (1, Accidental::Sharp),
+            4 => (2, Accidental::Natural),
+            5 => (3, Accidental::Natural),
+            6 => (3, Accidental::Sharp),
+            7 => (4, Accidental::Natural),
+            8 => (4, Accidental::Sharp),
+            9 => (5, Accidental::Natural),
+            10 => (5, Accidental::Sharp),
+            _ => (6, Accidental::Natural),
         };
+        // every step up the scale is half a staff space higher, counting from the E on the bottom line
+        const BOTTOM_LINE_STEP: i32 = 5 * 7 + 2;
+        let y_coord_on_staff = 4.0 - (((pitch / 12) as i32 * 7 + step_in_octave) - BOTTOM_LINE_STEP) as f32 * 0.5;
         let notehead_origin =
             optional_coord_to_tuple(self.font.metadata.anchors.get(Glyph::NoteheadBlack).and_then(|anchors| anchors.notehead_origin));
-        let stem_thickness =
-            self.font.metadata.engraving_defaults.stem_thickness.unwrap_or(StaffSpaces(3.0 / 25.0)).0 as f32 * self.staff_space as f32;
+        let stem_thickness = self.font.metadata.engraving_defaults.stem_thickness.unwrap_or(StaffSpaces(3.0 / 25.0)).0 as f32 * self.staff_space;
 
         // drawing the notehead
         {
             let (notehead_drawn_position, rotation) =
                 self.calculate_position(x_coord_on_staff - notehead_origin.x, y_coord_on_staff - notehead_origin.y);
 
-            draw_text_ex(
+            canvas.draw_text(
                 &Glyph::NoteheadBlack.codepoint().to_string(),
                 notehead_drawn_position.x,
                 notehead_drawn_position.y,
-                TextParams { rotation, ..self.font.make_text_params(self, note_color) },
+                &TextParams { rotation, ..self.font.make_text_params(self, note_color) },
             );
         }
 
@@ -144,12 +161,12 @@ impl<'font> Staff<'font> {
             let accidental_x = match self.position {
                 StaffPosition::Straight { top: _, left: _, right: _ } => x_coord_on_staff - ACCIDENTAL_SHIFT.0 as f32,
                 StaffPosition::Circular { center_x: _, center_y: _, outer_radius } => {
-                    x_coord_on_staff - d_staff_spaces_to_radians(outer_radius, self.staff_space as f32, ACCIDENTAL_SHIFT.0 as f32, y_coord_on_staff)
+                    x_coord_on_staff - d_staff_spaces_to_radians(outer_radius, self.staff_space, ACCIDENTAL_SHIFT.0 as f32, y_coord_on_staff)
                 }
             };
 
             let (accidental_position, accidental_rotation) = self.calculate_position(accidental_x, y_coord_on_staff);
-            draw_text_ex(
+            canvas.draw_text(
                 &match accidental {
                     Accidental::Natural => unreachable!(),
                     Accidental::Sharp => Glyph::AccidentalSharp,
@@ -159,7 +176,7 @@ impl<'font> Staff<'font> {
                 .to_string(),
                 accidental_position.x,
                 accidental_position.y,
-                TextParams { rotation: accidental_rotation, ..self.font.make_text_params(self, note_color) },
+                &TextParams { rotation: accidental_rotation, ..self.font.make_text_params(self, note_color) },
             );
         }
 
@@ -173,14 +190,14 @@ impl<'font> Staff<'font> {
         let stem_x = match self.position {
             StaffPosition::Straight { top: _, left: _, right: _ } => x_coord_on_staff + stem_origin.x,
             StaffPosition::Circular { center_x: _, center_y: _, outer_radius } => {
-                x_coord_on_staff + d_staff_spaces_to_radians(outer_radius, self.staff_space as f32, stem_origin.x, y_coord_on_staff)
+                x_coord_on_staff + d_staff_spaces_to_radians(outer_radius, self.staff_space, stem_origin.x, y_coord_on_staff)
             }
         };
         {
             let (stem_start_drawn_position, _) = self.calculate_position(stem_x, y_coord_on_staff + stem_origin.y);
             let (stem_end_drawn_position, _) = self.calculate_position(stem_x, stem_end_y);
 
-            draw_line(
+            canvas.draw_line(
                 stem_start_drawn_position.x,
                 stem_start_drawn_position.y,
                 stem_end_drawn_position.x,
@@ -211,23 +228,22 @@ impl<'font> Staff<'font> {
                             let (beam_left_drawn_position, _) = self.calculate_position(beam_left, current_y);
                             let (beam_right_drawn_position, _) = self.calculate_position(beam_right, current_y);
 
-                            draw_line(
+                            canvas.draw_line(
                                 beam_left_drawn_position.x,
                                 beam_left_drawn_position.y,
                                 beam_right_drawn_position.x,
                                 beam_right_drawn_position.y,
-                                beam_thickness * self.staff_space as f32,
+                                beam_thickness * self.staff_space,
                                 beam_color,
                             );
                         }
                         StaffPosition::Circular { center_x, center_y, outer_radius } => {
-                            draw_arc(
+                            canvas.draw_arc(
                                 center_x,
                                 center_y,
-                                48,
-                                radius_for_y(outer_radius, current_y, self.staff_space as f32),
+                                radius_for_y(outer_radius, current_y, self.staff_space),
                                 beam_left.to_degrees() - 90.0,
-                                beam_thickness * self.staff_space as f32,
+                                beam_thickness * self.staff_space,
                                 (beam_right - beam_left).to_degrees(),
                                 beam_color,
                             );
@@ -240,77 +256,71 @@ impl<'font> Staff<'font> {
         }
     }
 
-    pub fn draw_crescendo(&self, y: f32, left: f32, right: f32, color: Color) {
-        self.draw_hairpin(y, left, right, color, false, true);
-    }
+    // the accent is drawn centered on the notehead, on the other side of it than the stem
+    pub fn draw_accent(&self, canvas: &mut dyn Canvas, x_coord_on_staff: f32, stem_end_y: f32, color: Color) {
+        let (glyph, y) = if stem_end_y > 2.0 { (Glyph::ArticAccentAbove, ACCENT_ABOVE_Y) } else { (Glyph::ArticAccentBelow, ACCENT_BELOW_Y) };
+        let notehead_width = self.font.metadata.advance_widths.get(Glyph::NoteheadBlack).unwrap_or(StaffSpaces(1.18)).0 as f32;
+        let accent_width = self.font.metadata.advance_widths.get(glyph).unwrap_or(StaffSpaces(1.35)).0 as f32;
+        let shift = (notehead_width - accent_width) * 0.5;
+        let accent_x = match self.position {
+            StaffPosition::Straight { top: _, left: _, right: _ } => x_coord_on_staff + shift,
+            StaffPosition::Circular { center_x: _, center_y: _, outer_radius } => {
+                x_coord_on_staff + d_staff_spaces_to_radians(outer_radius, self.staff_space, shift, y)
+            }
+        };
 
-    pub fn draw_decrescendo(&self, y: f32, left: f32, right: f32, color: Color) {
-        self.draw_hairpin(y, left, right, color, true, false);
+        let (position, rotation) = self.calculate_position(accent_x, y);
+        canvas.draw_text(&glyph.codepoint().to_string(), position.x, position.y, &TextParams { rotation, ..self.font.make_text_params(self, color) });
     }
 
-    // using bool for true = 1, false = 0
-    fn draw_hairpin(&self, y: f32, left: f32, right: f32, color: Color, left_dynamic: bool, right_dynamic: bool) {
-        #![allow(clippy::bool_comparison)]
-        let hairpin_thickness =
-            self.font.metadata.engraving_defaults.hairpin_thickness.unwrap_or(StaffSpaces(0.16)).0 as f32 * self.staff_space as f32;
+    // the quieter end of the hairpin is closed and the louder end is open, with the dynamic level written at both ends
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_hairpin(&self, canvas: &mut dyn Canvas, y: f32, left: f32, right: f32, color: Color, from: DynamicLevel, to: DynamicLevel) {
+        let hairpin_thickness = self.font.metadata.engraving_defaults.hairpin_thickness.unwrap_or(StaffSpaces(0.16)).0 as f32 * self.staff_space;
 
-        let height_left = if left_dynamic == false { 0.0 } else { 1.0 };
-        let height_right = if right_dynamic == false { 0.0 } else { 1.0 };
+        let height_left = if from > to { 1.0 } else { 0.0 };
+        let height_right = if to > from { 1.0 } else { 0.0 };
 
         {
             let (top_line_left, _) = self.calculate_position(left, y - height_left / 2.0);
             let (top_line_right, _) = self.calculate_position(right, y - height_right / 2.0);
 
-            draw_line(top_line_left.x, top_line_left.y, top_line_right.x, top_line_right.y, hairpin_thickness, color);
+            canvas.draw_line(top_line_left.x, top_line_left.y, top_line_right.x, top_line_right.y, hairpin_thickness, color);
         }
         {
             let (bottom_line_left, _) = self.calculate_position(left, y + height_left / 2.0);
             let (bottom_line_right, _) = self.calculate_position(right, y + height_right / 2.0);
 
-            draw_line(bottom_line_left.x, bottom_line_left.y, bottom_line_right.x, bottom_line_right.y, hairpin_thickness, color);
+            canvas.draw_line(bottom_line_left.x, bottom_line_left.y, bottom_line_right.x, bottom_line_right.y, hairpin_thickness, color);
         }
 
-        if left_dynamic == false {
-            let n_advance_width = self.font.metadata.advance_widths.get(Glyph::DynamicNiente).unwrap_or(StaffSpaces(1.0)).0 as f32;
-            let (position, rotation) = self.calculate_position(left - 1.0 - n_advance_width, y + 0.5);
-            draw_text_ex(
-                &Glyph::DynamicNiente.codepoint().to_string(),
-                position.x,
-                position.y,
-                TextParams { rotation, ..self.font.make_text_params(self, color) },
-            );
-        }
-        if right_dynamic == false {
-            let (position, rotation) = self.calculate_position(right + 1.0, y + 0.5);
-            draw_text_ex(
-                &Glyph::DynamicNiente.codepoint().to_string(),
-                position.x,
-                position.y,
-                TextParams { rotation, ..self.font.make_text_params(self, color) },
-            );
-        }
-        if right_dynamic == true {
-            let (position, rotation) = self.calculate_position(right + 1.0, y + 0.5);
-            draw_text_ex(
-                &Glyph::DynamicMf.codepoint().to_string(),
-                position.x,
-                position.y,
-                TextParams { rotation, ..self.font.make_text_params(self, color) },
-            );
-        }
+        let from_advance_width = self.font.metadata.advance_widths.get(dynamic_glyph(from)).unwrap_or(StaffSpaces(1.0)).0 as f32;
+        self.draw_dynamic(canvas, left - 1.0 - from_advance_width, y, from, color);
+        self.draw_dynamic(canvas, right + 1.0, y, to, color);
     }
 
-    pub fn draw_starting_repeat_sign(&self, x: f32, color: Color) {
-        self.draw_repeat_sign(x, false, color);
+    // the x position is the left edge of the dynamic and the y position is the middle of it
+    pub fn draw_dynamic(&self, canvas: &mut dyn Canvas, x: f32, y: f32, level: DynamicLevel, color: Color) {
+        let (position, rotation) = self.calculate_position(x, y + 0.5);
+        canvas.draw_text(
+            &dynamic_glyph(level).codepoint().to_string(),
+            position.x,
+            position.y,
+            &TextParams { rotation, ..self.font.make_text_params(self, color) },
+        );
     }
-    pub fn draw_ending_repeat_sign(&self, x: f32, color: Color) {
-        self.draw_repeat_sign(x, true, color);
+
+    pub fn draw_starting_repeat_sign(&self, canvas: &mut dyn Canvas, x: f32, color: Color) {
+        self.draw_repeat_sign(canvas, x, false, color);
+    }
+    pub fn draw_ending_repeat_sign(&self, canvas: &mut dyn Canvas, x: f32, color: Color) {
+        self.draw_repeat_sign(canvas, x, true, color);
     }
 
     // the x position is the edge of the dots
     // for a starting repeat barline, the x is the right edge of the dots
     // for an ending repeat barline, the x is the left edge of the dots
-    fn draw_repeat_sign(&self, x: f32, ending: bool, color: Color) {
+    fn draw_repeat_sign(&self, canvas: &mut dyn Canvas, x: f32, ending: bool, color: Color) {
         let thin_barline_thickness = self.font.metadata.engraving_defaults.thin_barline_thickness.unwrap_or(StaffSpaces(0.16)).0 as f32;
         let thick_barline_thickness = self.font.metadata.engraving_defaults.thick_barline_thickness.unwrap_or(StaffSpaces(0.5)).0 as f32;
         let thin_thick_barline_separation = self.font.metadata.engraving_defaults.thin_thick_barline_separation.unwrap_or(StaffSpaces(0.4)).0 as f32;
@@ -343,28 +353,28 @@ impl<'font> Staff<'font> {
 
         let (Vec2 { x: thin_line_x, y: thin_line_top_y }, _) = self.calculate_position(thin_line_x, 0.0);
         let (Vec2 { x: _, y: thin_line_bottom_y }, _) = self.calculate_position(thin_line_x, 4.0);
-        draw_line(thin_line_x, thin_line_top_y, thin_line_x, thin_line_bottom_y, thin_barline_thickness * self.staff_space as f32, color);
+        canvas.draw_line(thin_line_x, thin_line_top_y, thin_line_x, thin_line_bottom_y, thin_barline_thickness * self.staff_space, color);
 
         let (Vec2 { x: thick_line_x, y: thick_line_top_y }, _) = self.calculate_position(thick_line_x, 0.0);
         let (Vec2 { x: _, y: thick_line_bottom_y }, _) = self.calculate_position(thick_line_x, 4.0);
-        draw_line(thick_line_x, thick_line_top_y, thick_line_x, thick_line_bottom_y, thick_barline_thickness * self.staff_space as f32, color);
+        canvas.draw_line(thick_line_x, thick_line_top_y, thick_line_x, thick_line_bottom_y, thick_barline_thickness * self.staff_space, color);
 
         let (Vec2 { x: dots_x, y: dots_y }, dots_rotation) = self.calculate_position(dots_x, 4.0);
-        draw_text_ex(
+        canvas.draw_text(
             &Glyph::RepeatDots.codepoint().to_string(),
             dots_x,
             dots_y,
-            TextParams { rotation: dots_rotation, ..self.font.make_text_params(self, color) },
+            &TextParams { rotation: dots_rotation, ..self.font.make_text_params(self, color) },
         );
     }
 
-    pub fn draw_treble_clef(&self, x: f32, color: Color) {
+    pub fn draw_treble_clef(&self, canvas: &mut dyn Canvas, x: f32, color: Color) {
         let (position, rotation) = self.calculate_position(x, 3.0);
-        draw_text_ex(
+        canvas.draw_text(
             &Glyph::GClef.codepoint().to_string(),
             position.x,
             position.y,
-            TextParams { rotation, ..self.font.make_text_params(self, color) },
+            &TextParams { rotation, ..self.font.make_text_params(self, color) },
         );
     }
 }
@@ -385,3 +395,17 @@ fn d_staff_spaces_to_radians(outer_radius: f32, staff_space: f32, d_staff_spaces
     let circle_radius = radius_for_y(outer_radius, y, staff_space);
     d_staff_spaces * staff_space / circle_radius
 }
+
+fn dynamic_glyph(level: DynamicLevel) -> Glyph {
+    match level {
+        DynamicLevel::Niente => Glyph::DynamicNiente,
+        DynamicLevel::Ppp => Glyph::DynamicPpp,
+        DynamicLevel::Pp => Glyph::DynamicPp,
+        DynamicLevel::P => Glyph::DynamicPiano,
+        DynamicLevel::Mp => Glyph::DynamicMp,
+        DynamicLevel::Mf => Glyph::DynamicMf,
+        DynamicLevel::F => Glyph::DynamicForte,
+        DynamicLevel::Ff => Glyph::DynamicFf,
+        DynamicLevel::Fff => Glyph::DynamicFff,
+    }
+}
@@ -0,0 +1,170 @@
+use itertools::Itertools;
+use macroquad::math::Rect;
+use num_rational::{Ratio, Rational32};
+use num_traits::ToPrimitive;
+
+use crate::{
+    music::{Dynamic, Part, PartNumber, PianoPhase, Segment},
+    visualizer::{
+        canvas::{Canvas, TextParams, Typeface},
+        colors::{ChangeAlpha, Theme},
+        draw_rehearsal_mark, draw_wheel_notes,
+        notation::{self, Staff, StaffPosition},
+    },
+};
+
+// the height of a cell compared to its width, with room for the two wheels side by side and two lines of text under them
+const CELL_ASPECT: f32 = 0.7;
+// the part of the height taken up by the title
+const TITLE_HEIGHT: f32 = 0.05;
+// parts that rest are still drawn so that the pattern can be seen, but faded out
+const SILENT_ALPHA: f32 = 0.25;
+
+// a stretch of the piece where neither part changes what it plays
+// the parts' segments do not start at the same times, since part 2 gets ahead while phasing and plays on its own to catch up,
+// so each part's segment is the one playing at the start of the step
+#[derive(Debug)]
+pub(super) struct Step {
+    pub start_time: Rational32,
+    pub end_time: Rational32,
+    pub part1: Option<usize>,
+    pub part2: Option<usize>,
+}
+
+impl Step {
+    // how often the segment's pattern is played during the step, to the nearest repetition
+    fn repetitions(&self, segment: &Segment) -> u32 {
+        ((self.end_time - self.start_time) * segment.speed / segment.pattern.0.len() as i32).round().to_integer() as u32
+    }
+}
+
+// a new step starts whenever either part changes its pattern, speed or dynamic
+pub(super) fn steps(music: &PianoPhase) -> Vec<Step> {
+    let changes = |part: &Part| {
+        let segments = &part.segments;
+        segments
+            .iter()
+            .enumerate()
+            .filter(|&(index, segment)| {
+                index == 0 || {
+                    let previous = &segments[index - 1];
+                    (&previous.pattern, previous.speed, previous.dynamic, previous.end_time)
+                        != (&segment.pattern, segment.speed, segment.dynamic, segment.start_time)
+                }
+            })
+            .map(|(_, segment)| segment.start_time)
+            .collect_vec()
+    };
+    let start_times = changes(&music.part1).into_iter().chain(changes(&music.part2)).sorted().dedup();
+    start_times
+        .chain([music.end_time()])
+        .tuple_windows()
+        .map(|(start_time, end_time)| {
+            let time = start_time.to_f32().unwrap();
+            Step { start_time, end_time, part1: music.part1.find_segment_for_time(time), part2: music.part2.find_segment_for_time(time) }
+        })
+        .collect()
+}
+
+// a map of the whole piece on one page, for the concert program
+// each step of the piece is a cell with the patterns of both parts on small wheels, with how often they are repeated and how far apart the parts are underneath
+pub fn draw_phase_chart(
+    canvas: &mut dyn Canvas,
+    notation_font: &notation::Font,
+    text_font: &Typeface,
+    theme: &Theme,
+    music: &PianoPhase,
+    window: Rect,
+) {
+    let steps = steps(music);
+    if steps.is_empty() {
+        return;
+    }
+
+    let title_height = window.h * TITLE_HEIGHT;
+    let title_font_size = (title_height * 0.6).ceil() as u16;
+    canvas.draw_text(
+        "Piano Phase",
+        window.x + title_font_size as f32 * 0.5,
+        window.y + title_height * 0.75,
+        &TextParams::new(text_font, title_font_size, theme.foreground),
+    );
+
+    let grid = Rect::new(window.x, window.y + title_height, window.w, window.h - title_height);
+    // as many columns as make the cells the biggest
+    let (columns, cell_width) = (1..=steps.len())
+        .map(|columns| (columns, f32::min(grid.w / columns as f32, grid.h / steps.len().div_ceil(columns) as f32 / CELL_ASPECT)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    let cell_height = cell_width * CELL_ASPECT;
+
+    for (index, step) in steps.iter().enumerate() {
+        let cell =
+            Rect::new(grid.x + (index % columns) as f32 * cell_width, grid.y + (index / columns) as f32 * cell_height, cell_width, cell_height);
+        draw_step(canvas, notation_font, text_font, theme, music, step, cell);
+    }
+}
+
+fn draw_step(
+    canvas: &mut dyn Canvas,
+    notation_font: &notation::Font,
+    text_font: &Typeface,
+    theme: &Theme,
+    music: &PianoPhase,
+    step: &Step,
+    cell: Rect,
+) {
+    let part1_segment = step.part1.map(|index| &music.part1.segments[index]);
+    let part2_segment = step.part2.map(|index| &music.part2.segments[index]);
+
+    // the same proportions as the wheels in the layouts
+    let wheel_radius = cell.w * 0.2;
+    let wheel_y = cell.y + cell.w * 0.25;
+    let font_size = (cell.w * 0.055).ceil() as u16;
+    let text_params = TextParams::new(text_font, font_size, theme.foreground);
+    let description_y = cell.y + cell.w * 0.5 + font_size as f32;
+
+    for (part_number, segment, center_x) in
+        [(PartNumber::Part1, part1_segment, cell.x + cell.w * 0.25), (PartNumber::Part2, part2_segment, cell.x + cell.w * 0.75)]
+    {
+        let Some(segment) = segment else { continue };
+        let staff =
+            Staff::new(notation_font, StaffPosition::Circular { center_x, center_y: wheel_y, outer_radius: wheel_radius }, wheel_radius * 0.15 / 4.0);
+        staff.draw(canvas, theme.foreground);
+        let alpha = if segment.dynamic == Dynamic::Silent { SILENT_ALPHA } else { 1.0 };
+        let note_color = theme.part(part_number).notes.modify_a(|a| a * alpha);
+        draw_wheel_notes(canvas, &staff, &segment.pattern, note_color, note_color, None);
+
+        let description = describe_segment(segment, step);
+        let width = canvas.measure_text(&description, &text_params).width;
+        canvas.draw_text(&description, center_x - width / 2.0, description_y, &text_params);
+    }
+
+    // the marks are at part 1's times, which can fall inside a step that part 2 started
+    if let Some(mark) = music.rehearsal_marks.iter().find(|mark| (step.start_time..step.end_time).contains(&mark.time)) {
+        draw_rehearsal_mark(canvas, text_font, theme, mark.number, cell.x + font_size as f32 * 0.5, cell.y + font_size as f32 * 1.5, font_size);
+    }
+
+    // the parts move one note further apart over each phasing passage
+    let Some(offset) = music.phase_offset(step.start_time.to_f32().unwrap()) else { return };
+    let offset = offset.round() as usize;
+    let offset_text = match (part1_segment, part2_segment) {
+        (Some(part1_segment), Some(part2_segment)) if part2_segment.speed != Ratio::ONE => {
+            format!("offset {offset}–{}", (offset + 1) % part1_segment.pattern.0.len())
+        }
+        _ => format!("offset {offset}"),
+    };
+    let width = canvas.measure_text(&offset_text, &text_params).width;
+    canvas.draw_text(&offset_text, cell.x + (cell.w - width) / 2.0, description_y + font_size as f32 * 1.4, &text_params);
+}
+
+fn describe_segment(segment: &Segment, step: &Step) -> String {
+    let status = if segment.dynamic == Dynamic::Silent {
+        "Tacet"
+    } else if segment.speed != Ratio::ONE {
+        "Phasing"
+    } else {
+        "Steady"
+    };
+    format!("{status} ×{}", step.repetitions(segment))
+}
@@ -0,0 +1,82 @@
+use macroquad::{color::Color, math::Rect};
+use num_traits::ToPrimitive;
+
+use crate::{
+    music::{FlattenedNote, Part, PianoPhase},
+    util::remap,
+    visualizer::{
+        canvas::Canvas,
+        colors::{ChangeAlpha, Theme},
+        keyboard::is_black_key,
+    },
+};
+
+// how many notes are shown on each side of the playhead
+const WINDOW_HALF_LENGTH: f32 = 24.0;
+
+// a scrolling piano roll of both parts with the playhead in the middle
+pub fn draw_piano_roll(canvas: &mut dyn Canvas, theme: &Theme, music: &PianoPhase, window: Rect, current_time: f32) {
+    let all_notes = || music.part1.flattened.iter().chain(&music.part2.flattened);
+    let (Some(lowest_pitch), Some(highest_pitch)) = (all_notes().map(|n| n.pitch).min(), all_notes().map(|n| n.pitch).max()) else {
+        return;
+    };
+    // one row of space above and below so that the highest and lowest notes are not on the edges
+    let lowest_row = lowest_pitch as f32 - 1.0;
+    let highest_row = highest_pitch as f32 + 1.0;
+    let row_height = window.h / (highest_row - lowest_row + 1.0);
+
+    let window_start = current_time - WINDOW_HALF_LENGTH;
+    let window_end = current_time + WINDOW_HALF_LENGTH;
+
+    let time_to_x = |time: f32| remap(time, window_start, window_end, window.x, window.x + window.w);
+    let pitch_to_y = |pitch: u8| remap(pitch as f32, highest_row, lowest_row, window.y, window.y + window.h - row_height);
+
+    // shade the rows of the black keys
+    for pitch in (lowest_row as u8)..=(highest_row as u8) {
+        if is_black_key(pitch) {
+            canvas.draw_rectangle(window.x, pitch_to_y(pitch), window.w, row_height, theme.foreground.modify_a(|a| a * 0.06));
+        }
+    }
+
+    let part1_notes = notes_in_window(&music.part1, window_start, window_end);
+    let part2_notes = notes_in_window(&music.part2, window_start, window_end);
+
+    let draw_notes = |canvas: &mut dyn Canvas, part: &Part, notes: &[FlattenedNote], color: Color| {
+        for note in notes {
+            let start = note.time.to_f32().unwrap();
+            let end = (note.time + note.length).to_f32().unwrap();
+            let x = time_to_x(start);
+            let y = pitch_to_y(note.pitch);
+            let w = time_to_x(end) - x;
+
+            canvas.draw_rectangle(x, y, w, row_height, color.modify_a(|a| a * note.relative_volume(part)));
+
+            // the notes that are currently sounding get an outline
+            if start <= current_time && current_time < end {
+                canvas.draw_rectangle_lines(x, y, w, row_height, row_height * 0.15, color);
+            }
+        }
+    };
+    draw_notes(canvas, &music.part1, part1_notes, theme.part1.identifying);
+    draw_notes(canvas, &music.part2, part2_notes, theme.part2.identifying);
+
+    // highlight the places where both parts are playing the same pitch at the same time
+    for part1_note in part1_notes {
+        for part2_note in part2_notes.iter().filter(|n| n.pitch == part1_note.pitch) {
+            let overlap_start = std::cmp::max(part1_note.time, part2_note.time);
+            let overlap_end = std::cmp::min(part1_note.time + part1_note.length, part2_note.time + part2_note.length);
+            if overlap_start < overlap_end {
+                let x = time_to_x(overlap_start.to_f32().unwrap());
+                let w = time_to_x(overlap_end.to_f32().unwrap()) - x;
+                canvas.draw_rectangle_lines(x, pitch_to_y(part1_note.pitch), w, row_height, row_height * 0.3, theme.unison);
+            }
+        }
+    }
+
+    let playhead_x = time_to_x(current_time);
+    canvas.draw_line(playhead_x, window.y, playhead_x, window.y + window.h, row_height * 0.1, theme.foreground);
+}
+
+fn notes_in_window(part: &Part, window_start: f32, window_end: f32) -> &[FlattenedNote] {
+    part.find_note_range(|n| (n.time + n.length).to_f32().unwrap() < window_start, |n| n.time.to_f32().unwrap() <= window_end)
+}
@@ -0,0 +1,178 @@
+use macroquad::{color::Color, text::TextDimensions, texture::Image};
+
+use crate::visualizer::canvas::{Canvas, TextParams};
+
+// each pixel of a shape is sampled this many times in each direction, for antialiasing
+const SUPERSAMPLING: usize = 4;
+
+// draws into an image in memory without a window, so that drawings can be made in tests and on machines without a display
+// the shapes are made to cover the same pixels as when macroquad draws them, but the edges are antialiased differently
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    // straight alpha, from the top left, a row at a time
+    pixels: Vec<Color>,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32) -> Raster {
+        Raster { width, height, pixels: vec![Color::new(0.0, 0.0, 0.0, 0.0); width as usize * height as usize] }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn to_image(&self) -> Image {
+        let bytes = self.pixels.iter().flat_map(|pixel| -> [u8; 4] { (*pixel).into() }).collect();
+        Image { bytes, width: self.width as u16, height: self.height as u16 }
+    }
+
+    // macroquad flips images when saving them because render targets are upside down, so the image is flipped first to be saved upright
+    pub fn export_png(&self, path: &str) {
+        let mut image = self.to_image();
+        let row_length = self.width as usize * 4;
+        let flipped = image.bytes.chunks(row_length).rev().flatten().copied().collect();
+        image.bytes = flipped;
+        image.export_png(path);
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: Color, coverage: f32) {
+        let source_alpha = color.a * coverage;
+        if source_alpha <= 0.0 {
+            return;
+        }
+        let pixel = &mut self.pixels[y * self.width as usize + x];
+        let alpha = source_alpha + pixel.a * (1.0 - source_alpha);
+        let mix = |source: f32, destination: f32| (source * source_alpha + destination * pixel.a * (1.0 - source_alpha)) / alpha;
+        *pixel = Color::new(mix(color.r, pixel.r), mix(color.g, pixel.g), mix(color.b, pixel.b), alpha);
+    }
+
+    // fills every pixel inside the bounds by how much of it is inside the shape
+    fn fill(&mut self, bounds: [f32; 4], color: Color, inside: impl Fn(f32, f32) -> bool) {
+        let [left, top, right, bottom] = bounds;
+        let clamp_x = |x: f32| x.clamp(0.0, self.width as f32) as usize;
+        let clamp_y = |y: f32| y.clamp(0.0, self.height as f32) as usize;
+        let (x_range, y_range) = (clamp_x(left.floor())..clamp_x(right.ceil()), clamp_y(top.floor())..clamp_y(bottom.ceil()));
+        for y in y_range {
+            for x in x_range.clone() {
+                let samples = (0..SUPERSAMPLING * SUPERSAMPLING)
+                    .filter(|sample| {
+                        let sample_x = x as f32 + ((sample % SUPERSAMPLING) as f32 + 0.5) / SUPERSAMPLING as f32;
+                        let sample_y = y as f32 + ((sample / SUPERSAMPLING) as f32 + 0.5) / SUPERSAMPLING as f32;
+                        inside(sample_x, sample_y)
+                    })
+                    .count();
+                self.blend(x, y, color, samples as f32 / (SUPERSAMPLING * SUPERSAMPLING) as f32);
+            }
+        }
+    }
+
+    fn fill_ring(&mut self, x: f32, y: f32, inner_radius: f32, outer_radius: f32, color: Color, angle_inside: impl Fn(f32) -> bool) {
+        let bounds = [x - outer_radius, y - outer_radius, x + outer_radius, y + outer_radius];
+        self.fill(bounds, color, |sample_x, sample_y| {
+            let distance = (sample_x - x).hypot(sample_y - y);
+            inner_radius <= distance && distance <= outer_radius && angle_inside((sample_y - y).atan2(sample_x - x))
+        });
+    }
+}
+
+impl Canvas for Raster {
+    fn clear(&mut self, color: Color) {
+        self.pixels.fill(color);
+    }
+
+    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let length = (x2 - x1).hypot(y2 - y1);
+        if length == 0.0 {
+            return;
+        }
+        let (direction_x, direction_y) = ((x2 - x1) / length, (y2 - y1) / length);
+        let half_thickness = thickness / 2.0;
+        let bounds = [x1.min(x2) - half_thickness, y1.min(y2) - half_thickness, x1.max(x2) + half_thickness, y1.max(y2) + half_thickness];
+        self.fill(bounds, color, |x, y| {
+            let along = (x - x1) * direction_x + (y - y1) * direction_y;
+            let across = (x - x1) * -direction_y + (y - y1) * direction_x;
+            (0.0..=length).contains(&along) && across.abs() <= half_thickness
+        });
+    }
+
+    fn draw_rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        let (left, right) = (x.min(x + w), x.max(x + w));
+        let (top, bottom) = (y.min(y + h), y.max(y + h));
+        self.fill([left, top, right, bottom], color, |sample_x, sample_y| (left..=right).contains(&sample_x) && (top..=bottom).contains(&sample_y));
+    }
+
+    fn draw_rectangle_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        let inset = thickness / 2.0;
+        self.fill([x, y, x + w, y + h], color, |sample_x, sample_y| {
+            let inside_outer = (x..=x + w).contains(&sample_x) && (y..=y + h).contains(&sample_y);
+            let inside_inner = (x + inset..=x + w - inset).contains(&sample_x) && (y + inset..=y + h - inset).contains(&sample_y);
+            inside_outer && !inside_inner
+        });
+    }
+
+    fn draw_circle(&mut self, x: f32, y: f32, r: f32, color: Color) {
+        self.fill_ring(x, y, 0.0, r, color, |_| true);
+    }
+
+    fn draw_circle_lines(&mut self, x: f32, y: f32, r: f32, thickness: f32, color: Color) {
+        self.fill_ring(x, y, r, r + thickness, color, |_| true);
+    }
+
+    fn draw_arc(&mut self, x: f32, y: f32, radius: f32, rotation: f32, thickness: f32, arc: f32, color: Color) {
+        let (start, arc) = if arc < 0.0 { ((rotation + arc).to_radians(), -arc.to_radians()) } else { (rotation.to_radians(), arc.to_radians()) };
+        self.fill_ring(x, y, radius, radius + thickness, color, |angle| (angle - start).rem_euclid(std::f32::consts::TAU) <= arc);
+    }
+
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, params: &TextParams) -> TextDimensions {
+        let (sin, cos) = params.rotation.sin_cos();
+        let px = params.font_size as f32 * params.font_scale;
+        let mut pen_x = 0.0;
+        for character in text.chars() {
+            let (metrics, bitmap) = params.typeface.font.rasterize(character, px);
+            // where the top left of the glyph is before rotating, relative to the start of the baseline
+            let (glyph_left, glyph_top) = (pen_x + metrics.xmin as f32, -(metrics.ymin as f32 + metrics.height as f32));
+            pen_x += metrics.advance_width;
+            if metrics.width == 0 || metrics.height == 0 {
+                continue;
+            }
+
+            let corners = [(0.0, 0.0), (metrics.width as f32, 0.0), (0.0, metrics.height as f32), (metrics.width as f32, metrics.height as f32)]
+                .map(|(dx, dy)| (x + (glyph_left + dx) * cos - (glyph_top + dy) * sin, y + (glyph_left + dx) * sin + (glyph_top + dy) * cos));
+            let left = corners.iter().map(|corner| corner.0).fold(f32::MAX, f32::min).floor().max(0.0) as usize;
+            let right = corners.iter().map(|corner| corner.0).fold(f32::MIN, f32::max).ceil().clamp(0.0, self.width as f32) as usize;
+            let top = corners.iter().map(|corner| corner.1).fold(f32::MAX, f32::min).floor().max(0.0) as usize;
+            let bottom = corners.iter().map(|corner| corner.1).fold(f32::MIN, f32::max).ceil().clamp(0.0, self.height as f32) as usize;
+
+            let coverage_at = |glyph_x: i64, glyph_y: i64| {
+                if (0..metrics.width as i64).contains(&glyph_x) && (0..metrics.height as i64).contains(&glyph_y) {
+                    bitmap[glyph_y as usize * metrics.width + glyph_x as usize] as f32 / 255.0
+                } else {
+                    0.0
+                }
+            };
+            for pixel_y in top..bottom {
+                for pixel_x in left..right {
+                    // the center of the pixel rotated back into the glyph's bitmap, which is then sampled bilinearly
+                    let (dx, dy) = (pixel_x as f32 + 0.5 - x, pixel_y as f32 + 0.5 - y);
+                    let glyph_x = dx * cos + dy * sin - glyph_left - 0.5;
+                    let glyph_y = -dx * sin + dy * cos - glyph_top - 0.5;
+                    let (x0, y0) = (glyph_x.floor(), glyph_y.floor());
+                    let (tx, ty) = (glyph_x - x0, glyph_y - y0);
+                    let (x0, y0) = (x0 as i64, y0 as i64);
+                    let coverage = coverage_at(x0, y0) * (1.0 - tx) * (1.0 - ty)
+                        + coverage_at(x0 + 1, y0) * tx * (1.0 - ty)
+                        + coverage_at(x0, y0 + 1) * (1.0 - tx) * ty
+                        + coverage_at(x0 + 1, y0 + 1) * tx * ty;
+                    self.blend(pixel_x, pixel_y, params.color, coverage);
+                }
+            }
+        }
+        self.measure_text(text, params)
+    }
+
+    fn measure_text(&self, text: &str, params: &TextParams) -> TextDimensions {
+        params.typeface.measure_text(text, params.font_size, params.font_scale)
+    }
+}
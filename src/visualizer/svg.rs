@@ -0,0 +1,173 @@
+use std::{fmt::Write, path::Path};
+
+use macroquad::{color::Color, text::TextDimensions};
+
+use crate::{
+    error::ExportError,
+    visualizer::canvas::{Canvas, TextParams, Typeface},
+};
+
+// draws into an svg image, so that a moment of the piece can be printed at any size
+// the text is kept as text in the fonts that it is drawn with, which are embedded in the image so that it looks the same everywhere
+pub struct Svg {
+    pub width: f32,
+    pub height: f32,
+    elements: String,
+    // the fonts that have been used, by their family name in the image, with their format and bytes
+    fonts: Vec<(String, &'static str, Vec<u8>)>,
+}
+
+impl Svg {
+    pub fn new(width: f32, height: f32) -> Svg {
+        Svg { width, height, elements: String::new(), fonts: Vec::new() }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ExportError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_string()).map_err(|source| ExportError::Write { path: path.to_owned(), source })
+    }
+
+    fn push(&mut self, element: std::fmt::Arguments) {
+        // writing to a string cannot fail
+        let _ = self.elements.write_fmt(element);
+        self.elements.push('\n');
+    }
+
+    fn font_family(&mut self, typeface: &Typeface) -> String {
+        let family = typeface.path.file_stem().map_or("font".into(), |stem| stem.to_string_lossy().into_owned());
+        if !self.fonts.iter().any(|(name, _, _)| *name == family) {
+            let format = if typeface.path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("otf")) { "otf" } else { "ttf" };
+            self.fonts.push((family.clone(), format, typeface.bytes().to_vec()));
+        }
+        family
+    }
+}
+
+impl std::fmt::Display for Svg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (width, height) = (self.width, self.height);
+        writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#)?;
+        if !self.fonts.is_empty() {
+            writeln!(f, "<style>")?;
+            for (family, format, bytes) in &self.fonts {
+                writeln!(f, r#"@font-face {{ font-family: "{family}"; src: url("data:font/{format};base64,{}"); }}"#, base64(bytes))?;
+            }
+            writeln!(f, "</style>")?;
+        }
+        write!(f, "{}", self.elements)?;
+        writeln!(f, "</svg>")
+    }
+}
+
+impl Canvas for Svg {
+    fn clear(&mut self, color: Color) {
+        self.elements.clear();
+        let (width, height) = (self.width, self.height);
+        self.push(format_args!(r#"<rect width="{width}" height="{height}" {}/>"#, fill(color)));
+    }
+
+    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        self.push(format_args!(r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" {}/>"#, stroke(color, thickness)));
+    }
+
+    fn draw_rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        // svg rectangles cannot have a negative size
+        let (x, w) = if w < 0.0 { (x + w, -w) } else { (x, w) };
+        let (y, h) = if h < 0.0 { (y + h, -h) } else { (y, h) };
+        self.push(format_args!(r#"<rect x="{x}" y="{y}" width="{w}" height="{h}" {}/>"#, fill(color)));
+    }
+
+    fn draw_rectangle_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        // the stroke is centered on the outline, so the outline is moved in to keep the whole line inside of the rectangle
+        let line_width = thickness / 2.0;
+        let inset = line_width / 2.0;
+        self.push(format_args!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" {}/>"#,
+            x + inset,
+            y + inset,
+            (w - line_width).max(0.0),
+            (h - line_width).max(0.0),
+            stroke(color, line_width)
+        ));
+    }
+
+    fn draw_circle(&mut self, x: f32, y: f32, r: f32, color: Color) {
+        self.push(format_args!(r#"<circle cx="{x}" cy="{y}" r="{r}" {}/>"#, fill(color)));
+    }
+
+    fn draw_circle_lines(&mut self, x: f32, y: f32, r: f32, thickness: f32, color: Color) {
+        self.push(format_args!(r#"<circle cx="{x}" cy="{y}" r="{}" fill="none" {}/>"#, r + thickness / 2.0, stroke(color, thickness)));
+    }
+
+    fn draw_arc(&mut self, x: f32, y: f32, radius: f32, rotation: f32, thickness: f32, arc: f32, color: Color) {
+        let middle_radius = radius + thickness / 2.0;
+        if arc.abs() >= 360.0 {
+            self.draw_circle_lines(x, y, radius, thickness, color);
+            return;
+        }
+        if arc == 0.0 {
+            return;
+        }
+        let point = |degrees: f32| {
+            let angle = degrees.to_radians();
+            (x + angle.cos() * middle_radius, y + angle.sin() * middle_radius)
+        };
+        let ((start_x, start_y), (end_x, end_y)) = (point(rotation), point(rotation + arc));
+        let large_arc = (arc.abs() > 180.0) as u8;
+        let clockwise = (arc > 0.0) as u8;
+        self.push(format_args!(
+            r#"<path d="M {start_x} {start_y} A {middle_radius} {middle_radius} 0 {large_arc} {clockwise} {end_x} {end_y}" fill="none" {}/>"#,
+            stroke(color, thickness)
+        ));
+    }
+
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, params: &TextParams) -> TextDimensions {
+        let family = self.font_family(params.typeface);
+        let font_size = params.font_size as f32 * params.font_scale;
+        let rotation =
+            if params.rotation != 0.0 { format!(r#" transform="rotate({} {x} {y})""#, params.rotation.to_degrees()) } else { String::new() };
+        self.push(format_args!(
+            r#"<text x="{x}" y="{y}" font-family="{family}" font-size="{font_size}"{rotation} {}>{}</text>"#,
+            fill(params.color),
+            escape(text)
+        ));
+        self.measure_text(text, params)
+    }
+
+    fn measure_text(&self, text: &str, params: &TextParams) -> TextDimensions {
+        params.typeface.measure_text(text, params.font_size, params.font_scale)
+    }
+}
+
+fn rgb(color: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = color.into();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn fill(color: Color) -> String {
+    format!(r#"fill="{}" fill-opacity="{}""#, rgb(color), color.a)
+}
+
+fn stroke(color: Color, width: f32) -> String {
+    format!(r#"stroke="{}" stroke-opacity="{}" stroke-width="{width}""#, rgb(color), color.a)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,321 @@
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use macroquad::{
+    color::{BLACK, WHITE},
+    math::Rect,
+    texture::Image,
+};
+use num_rational::Ratio;
+
+use super::*;
+use crate::{
+    music::{Dynamic, DynamicLevel},
+    visualizer::{raster::Raster, svg::Svg},
+};
+
+// the reference images are in this directory, named after the tests
+// after an intended change to the drawing, rewrite them by running the tests with UPDATE_GOLDEN_FILES=1 and look at the new images
+const REFERENCE_DIR: &str = "src/visualizer/reference";
+// how far apart the channels of a pixel can be before it counts as different, out of 255
+// this allows for small differences in how the glyphs are rasterized and in floating point rounding
+const CHANNEL_TOLERANCE: u8 = 24;
+// how many of the pixels can be different before the images count as different
+const DIFFERENT_PIXELS_TOLERANCE: f32 = 0.002;
+// the time of the frames, in the middle of the first phase so that the parts are out of sync
+const FRAME_TIME: f32 = 300.5;
+const LAYOUTS: [(&str, Layout); 5] = [
+    ("square", layout::SQUARE),
+    ("landscape", layout::LANDSCAPE),
+    ("vertical", layout::VERTICAL),
+    ("piano_roll", layout::PIANO_ROLL),
+    ("wheels", layout::WHEELS),
+];
+
+fn besley() -> Typeface {
+    Typeface::load("data/Besley/static/Besley-Regular.ttf").unwrap()
+}
+
+// the bravura submodule is not always checked out, so the tests that draw music notation are ignored without it (see build.rs)
+fn bravura() -> notation::Font {
+    notation::Font::load_bravura().unwrap_or_else(|error| panic!("could not load bravura ({error}); check it out with git submodule update --init"))
+}
+
+fn assert_matches_reference(name: &str, raster: &Raster) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(REFERENCE_DIR).join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN_FILES").is_some() {
+        raster.export_png(&path.to_string_lossy());
+    }
+    let reference = load_png(&path);
+    let actual = raster.to_image();
+    assert_eq!((actual.width, actual.height), (reference.width, reference.height), "{name} is a different size than {}", path.display());
+
+    let different_pixels = actual
+        .get_image_data()
+        .iter()
+        .zip(reference.get_image_data())
+        .filter(|(actual, reference)| actual.iter().zip(reference.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+        .count();
+    let different_fraction = different_pixels as f32 / (actual.width as f32 * actual.height as f32);
+    if different_fraction > DIFFERENT_PIXELS_TOLERANCE {
+        let actual_path = std::env::temp_dir().join(format!("pianophase-{name}.png"));
+        raster.export_png(&actual_path.to_string_lossy());
+        panic!(
+            "{:.2}% of the pixels of {name} are different than {}; the drawing was saved to {}",
+            different_fraction * 100.0,
+            path.display(),
+            actual_path.display()
+        );
+    }
+}
+
+fn load_png(path: &Path) -> Image {
+    let bytes =
+        std::fs::read(path).unwrap_or_else(|error| panic!("could not read {}: {error}; run with UPDATE_GOLDEN_FILES=1 to create it", path.display()));
+    Image::from_file_with_format(&bytes, Some(macroquad::prelude::ImageFormat::Png)).unwrap()
+}
+
+fn canvas(width: u32, height: u32) -> Raster {
+    let mut raster = Raster::new(width, height);
+    raster.clear(WHITE);
+    raster
+}
+
+fn straight_staff(font: &notation::Font) -> Staff<'_> {
+    Staff::new(font, StaffPosition::Straight { top: 40.0, left: 10.0, right: 310.0 }, 10.0)
+}
+
+fn circular_staff(font: &notation::Font) -> Staff<'_> {
+    Staff::new(font, StaffPosition::Circular { center_x: 160.0, center_y: 160.0, outer_radius: 140.0 }, 5.0)
+}
+
+#[test]
+fn raster_fills_exactly_the_shapes() {
+    let mut raster = canvas(8, 8);
+    raster.draw_rectangle(2.0, 2.0, 4.0, 4.0, BLACK);
+    assert_eq!(raster.pixel(1, 1), WHITE);
+    assert_eq!(raster.pixel(2, 2), BLACK);
+    assert_eq!(raster.pixel(5, 5), BLACK);
+    assert_eq!(raster.pixel(6, 6), WHITE);
+
+    // half of the pixel is covered
+    let mut raster = canvas(8, 8);
+    raster.draw_line(0.0, 4.0, 8.0, 4.0, 1.0, BLACK);
+    let half_covered = raster.pixel(3, 3);
+    assert!((half_covered.r - 0.5).abs() < 0.1, "{half_covered:?}");
+
+    // a quarter of a ring, clockwise from the right
+    let mut raster = canvas(20, 20);
+    raster.draw_arc(10.0, 10.0, 5.0, 0.0, 3.0, 90.0, BLACK);
+    assert_eq!(raster.pixel(16, 10), BLACK);
+    assert_eq!(raster.pixel(10, 16), BLACK);
+    assert_eq!(raster.pixel(3, 10), WHITE);
+    assert_eq!(raster.pixel(10, 3), WHITE);
+}
+
+#[test]
+fn rehearsal_mark() {
+    let besley = besley();
+    let mut raster = canvas(80, 60);
+    draw_rehearsal_mark(&mut raster, &besley, &Theme::default(), 12, 10.0, 45.0, 32);
+    assert_matches_reference("rehearsal_mark", &raster);
+}
+
+#[test]
+fn phase_diagram() {
+    let besley = besley();
+    let music = PianoPhase::new(432);
+    let mut raster = canvas(400, 100);
+    draw_phase_diagram(&mut raster, &besley, &Theme::default(), &music, Rect::new(0.0, 0.0, 400.0, 100.0), FRAME_TIME);
+    assert_matches_reference("phase_diagram", &raster);
+}
+
+#[test]
+fn keyboard() {
+    let besley = besley();
+    let music = PianoPhase::new(432);
+    let mut raster = canvas(240, 120);
+    keyboard::draw_keyboard(&mut raster, &besley, &Theme::default(), &music, Rect::new(0.0, 0.0, 240.0, 120.0), FRAME_TIME);
+    assert_matches_reference("keyboard", &raster);
+}
+
+#[test]
+fn piano_roll() {
+    let music = PianoPhase::new(432);
+    let mut raster = canvas(320, 160);
+    piano_roll::draw_piano_roll(&mut raster, &Theme::default(), &music, Rect::new(0.0, 0.0, 320.0, 160.0), FRAME_TIME);
+    assert_matches_reference("piano_roll", &raster);
+}
+
+#[test]
+fn theme_files_fill_in_the_light_theme() {
+    let path = std::env::temp_dir().join(format!("pianophase-{}-theme.json", std::process::id()));
+    std::fs::write(&path, r##"{ "background": "#000000", "part1": { "notes": "#ff0000" } }"##).unwrap();
+    let theme = Theme::load(&path.to_string_lossy());
+    std::fs::remove_file(&path).unwrap();
+    let theme = theme.unwrap();
+
+    let light = colors::LIGHT_THEME;
+    assert_eq!(theme.background, BLACK);
+    assert_eq!(theme.foreground, light.foreground);
+    // the colors of a part that are left out come from the same part of the light theme
+    assert_eq!(theme.part1.notes, Color::new(1.0, 0.0, 0.0, 1.0));
+    assert_eq!(theme.part1.current_note, light.part1.current_note);
+    assert_eq!(theme.part1.identifying, light.part1.identifying);
+    assert_eq!(theme.part2.identifying, light.part2.identifying);
+}
+
+#[test]
+fn svg_keeps_the_text_and_embeds_the_font() {
+    let besley = besley();
+    let mut svg = Svg::new(80.0, 60.0);
+    svg.clear(WHITE);
+    draw_rehearsal_mark(&mut svg, &besley, &Theme::default(), 12, 10.0, 45.0, 32);
+    svg.draw_arc(40.0, 30.0, 10.0, 0.0, 2.0, 90.0, BLACK);
+    let svg = svg.to_string();
+    assert!(svg.starts_with("<svg "), "{svg}");
+    assert!(svg.trim_end().ends_with("</svg>"), "{svg}");
+    assert!(svg.contains(r#"font-family="Besley-Regular""#), "{svg}");
+    assert!(svg.contains(">12</text>"), "{svg}");
+    // the font is embedded once, however often it is used
+    assert_eq!(svg.matches("@font-face").count(), 1);
+    // a quarter of a circle from the right to the bottom, along the middle of the ring
+    assert!(svg.contains("<path d=\"M 51 30 A 11 11 0 0 1 "), "{svg}");
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn notes_on_a_straight_staff() {
+    let font = bravura();
+    let staff = straight_staff(&font);
+    let mut raster = canvas(320, 140);
+    staff.draw(&mut raster, BLACK);
+    staff.draw_treble_clef(&mut raster, notation::CLEF_OFFSET, BLACK);
+    // a natural and a sharp on each side of the middle of the staff, with beams to both sides and stems both ways
+    for (i, pitch) in [64, 66, 71, 73, 77].into_iter().enumerate() {
+        let x = 10.0 + i as f32 * 4.0;
+        let stem_end_y = if i % 2 == 0 { STEM_ABOVE_Y } else { STEM_BELOW_Y };
+        staff.draw_note(&mut raster, x, pitch, BLACK, BLACK, stem_end_y, 2, Some(x - 2.0), Some(x + 2.0));
+    }
+    assert_matches_reference("notes_on_a_straight_staff", &raster);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn notes_on_a_circular_staff() {
+    let font = bravura();
+    let staff = circular_staff(&font);
+    let mut raster = canvas(320, 320);
+    staff.draw(&mut raster, BLACK);
+    // the notes are rotated with the staff, and the accidentals and beams are moved along the circle
+    for (i, pitch) in [64, 66, 71, 73, 74, 66, 64, 73, 71, 66, 74, 73].into_iter().enumerate() {
+        let angle = i as f32 / 12.0 * std::f32::consts::TAU;
+        let stem_end_y = if i % 2 == 0 { STEM_ABOVE_Y } else { STEM_BELOW_Y };
+        staff.draw_note(&mut raster, angle, pitch, BLACK, BLACK, stem_end_y, 2, None, Some(angle + 0.2));
+    }
+    assert_matches_reference("notes_on_a_circular_staff", &raster);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn repeat_signs() {
+    let font = bravura();
+    let staff = straight_staff(&font);
+    let mut raster = canvas(320, 140);
+    staff.draw(&mut raster, BLACK);
+    staff.draw_starting_repeat_sign(&mut raster, 8.0, BLACK);
+    staff.draw_ending_repeat_sign(&mut raster, 20.0, BLACK);
+    assert_matches_reference("repeat_signs", &raster);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn hairpins_and_dynamics() {
+    let font = bravura();
+    let staff = Staff::new(&font, StaffPosition::Straight { top: 10.0, left: 10.0, right: 310.0 }, 10.0);
+    let mut raster = canvas(320, 200);
+    staff.draw_hairpin(&mut raster, 2.0, 6.0, 14.0, BLACK, DynamicLevel::Niente, DynamicLevel::Mf);
+    staff.draw_hairpin(&mut raster, 6.0, 18.0, 26.0, BLACK, DynamicLevel::F, DynamicLevel::Pp);
+    staff.draw_dynamic(&mut raster, 4.0, 10.0, DynamicLevel::Mp, BLACK);
+    assert_matches_reference("hairpins_and_dynamics", &raster);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn whole_frame() {
+    let visualizer = Visualizer::new(layout::SQUARE, "light").unwrap();
+    let music = PianoPhase::new(432);
+    let mut raster = Raster::new(512, 512);
+    visualizer.draw(&mut raster, FRAME_TIME, &music, None, 512.0, 512.0);
+    assert_matches_reference("whole_frame", &raster);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn every_layout() {
+    let music = PianoPhase::new(432);
+    for (name, layout) in LAYOUTS {
+        let visualizer = Visualizer::new(layout, "light").unwrap();
+        let height = layout.height_for_width(512);
+        let mut raster = Raster::new(512, height);
+        visualizer.draw(&mut raster, FRAME_TIME, &music, None, 512.0, height as f32);
+        assert_matches_reference(&format!("layout_{name}"), &raster);
+    }
+}
+
+#[test]
+fn every_layout_places_its_panels_inside_the_drawing() {
+    for (name, layout) in LAYOUTS {
+        for placement in layout.panels {
+            let area = placement.area;
+            let inside = area.x >= 0.0 && area.y >= 0.0 && area.w > 0.0 && area.h > 0.0 && area.x + area.w <= 1.0 && area.y + area.h <= 1.0;
+            assert!(inside, "{:?} is placed outside of the {name} layout at {area:?}", placement.panel);
+        }
+        let (width, height) = layout.aspect_ratio;
+        assert_eq!(layout.height_for_width(width * 120), height * 120, "{name}");
+    }
+    assert_eq!(layout::LANDSCAPE.height_for_width(1920), 1080);
+    assert_eq!(layout::VERTICAL.height_for_width(1080), 1920);
+}
+
+#[test]
+#[cfg_attr(not(bravura), ignore = "the bravura submodule is not checked out")]
+fn phase_chart() {
+    let visualizer = Visualizer::new(layout::SQUARE, "light").unwrap();
+    let music = PianoPhase::new_shortened(432);
+    let mut raster = Raster::new(420, 594);
+    visualizer.draw_phase_chart(&mut raster, &music, 420.0, 594.0);
+    assert_matches_reference("phase_chart", &raster);
+}
+
+#[test]
+fn phase_chart_pairs_the_segments_playing_at_the_same_time() {
+    let music = PianoPhase::new(432);
+    let steps = phase_chart::steps(&music);
+    assert_eq!(steps.first().unwrap().start_time, Ratio::ZERO);
+    assert_eq!(steps.last().unwrap().end_time, music.end_time());
+    for (step, next) in steps.iter().tuple_windows() {
+        assert_eq!(step.end_time, next.start_time);
+    }
+
+    for step in &steps {
+        for (part, index) in [(&music.part1, step.part1), (&music.part2, step.part2)] {
+            let segment = &part.segments[index.unwrap()];
+            assert!(segment.start_time <= step.start_time && step.start_time < segment.end_time, "{step:?} is paired with {segment:?}");
+        }
+    }
+
+    // every phasing passage of part 2 is a step of its own, also the ones after part 2 has caught up on its own
+    let phasing_segments = music.part2.segments.iter().filter(|segment| segment.speed != Ratio::ONE).count();
+    let phasing_steps = steps.iter().filter(|step| music.part2.segments[step.part2.unwrap()].speed != Ratio::ONE).collect_vec();
+    assert_eq!(phasing_steps.len(), phasing_segments);
+    for step in phasing_steps {
+        let part1_segment = &music.part1.segments[step.part1.unwrap()];
+        let part2_segment = &music.part2.segments[step.part2.unwrap()];
+        assert_eq!((step.start_time, step.end_time), (part2_segment.start_time, part2_segment.end_time));
+        // while part 2 phases, part 1 keeps playing steadily
+        assert_eq!(part1_segment.speed, Ratio::ONE);
+        assert_ne!(part1_segment.dynamic, Dynamic::Silent);
+    }
+}